@@ -0,0 +1,314 @@
+use nih_plug_egui::egui;
+use std::{
+    f32::consts::TAU,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+
+//
+// SampleRing
+//
+
+// single-producer ring buffer of the latest output samples (mixed down to mono).
+// audio thread never blocks and gui thread copies the latest window whenever it repaints,
+// which might be torn by concurrent writes but that's harmless for visualization.
+pub struct SampleRing {
+    // f32 bits
+    samples: Vec<AtomicU32>,
+    // total number of written samples (wrapping)
+    write_position: AtomicUsize,
+    sample_rate: AtomicU32,
+}
+
+impl SampleRing {
+    // `capacity` is rounded up to power of two
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.next_power_of_two();
+        Self {
+            samples: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            write_position: AtomicUsize::new(0),
+            sample_rate: AtomicU32::new(44100f32.to_bits()),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn set_sample_rate(&self, sample_rate: f32) {
+        self.sample_rate
+            .store(sample_rate.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn sample_rate(&self) -> f32 {
+        f32::from_bits(self.sample_rate.load(Ordering::Relaxed))
+    }
+
+    // audio thread
+    pub fn push_buffer(&self, buffer: &[&mut [f32]]) {
+        let num_samples = buffer.first().map_or(0, |channel| channel.len());
+        let scale = (buffer.len() as f32).recip();
+        let mask = self.samples.len() - 1;
+        let mut position = self.write_position.load(Ordering::Relaxed);
+        for index in 0..num_samples {
+            let mono: f32 = buffer.iter().map(|channel| channel[index]).sum::<f32>() * scale;
+            self.samples[position & mask].store(mono.to_bits(), Ordering::Relaxed);
+            position = position.wrapping_add(1);
+        }
+        self.write_position.store(position, Ordering::Release);
+    }
+
+    // gui thread (copy latest `output.len()` samples in chronological order)
+    pub fn read_latest(&self, output: &mut [f32]) {
+        assert!(output.len() <= self.samples.len());
+        let mask = self.samples.len() - 1;
+        let end = self.write_position.load(Ordering::Acquire);
+        let start = end.wrapping_sub(output.len());
+        for (index, value) in output.iter_mut().enumerate() {
+            let position = start.wrapping_add(index) & mask;
+            *value = f32::from_bits(self.samples[position].load(Ordering::Relaxed));
+        }
+    }
+}
+
+//
+// AnalyzerView
+//
+
+const SCOPE_WINDOW: usize = 1024;
+const FFT_SIZE: usize = 2048;
+const SPECTRUM_MIN_FREQUENCY: f32 = 20.0;
+const SPECTRUM_MAX_FREQUENCY: f32 = 20000.0;
+const SPECTRUM_MIN_DB: f32 = -90.0;
+const SPECTRUM_MAX_DB: f32 = 0.0;
+
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub enum ViewMode {
+    Oscilloscope,
+    Spectrum,
+}
+
+// gui state (kept as egui editor's user state to reuse scratch buffers)
+pub struct AnalyzerView {
+    mode: ViewMode,
+    samples: Vec<f32>,
+    fft_real: Vec<f32>,
+    fft_imag: Vec<f32>,
+}
+
+impl AnalyzerView {
+    pub fn new() -> Self {
+        Self {
+            mode: ViewMode::Oscilloscope,
+            samples: vec![0.0; FFT_SIZE.max(2 * SCOPE_WINDOW)],
+            fft_real: vec![0.0; FFT_SIZE],
+            fft_imag: vec![0.0; FFT_SIZE],
+        }
+    }
+
+    pub fn ui(&mut self, ui: &mut egui::Ui, ring: &SampleRing, size: egui::Vec2) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.mode, ViewMode::Oscilloscope, "Scope");
+            ui.selectable_value(&mut self.mode, ViewMode::Spectrum, "Spectrum");
+        });
+
+        let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+
+        match self.mode {
+            ViewMode::Oscilloscope => self.oscilloscope(&painter, rect, ring),
+            ViewMode::Spectrum => self.spectrum(&painter, rect, ring),
+        }
+
+        // keep animating while visible
+        ui.ctx().request_repaint();
+    }
+
+    fn oscilloscope(&mut self, painter: &egui::Painter, rect: egui::Rect, ring: &SampleRing) {
+        let samples = &mut self.samples[..2 * SCOPE_WINDOW];
+        ring.read_latest(samples);
+
+        // sync to the latest rising zero-crossing which leaves full window after it (free-run if none)
+        let trigger = (1..=SCOPE_WINDOW)
+            .rev()
+            .find(|&index| samples[index - 1] < 0.0 && samples[index] >= 0.0)
+            .unwrap_or(SCOPE_WINDOW);
+        let window = &samples[trigger..(trigger + SCOPE_WINDOW)];
+
+        painter.line_segment(
+            [rect.left_center(), rect.right_center()],
+            egui::Stroke::new(1.0, egui::Color32::from_gray(60)),
+        );
+        let points: Vec<egui::Pos2> = window
+            .iter()
+            .enumerate()
+            .map(|(index, &value)| {
+                let x = rect.left() + rect.width() * (index as f32) / (SCOPE_WINDOW as f32);
+                let y = rect.center().y - 0.5 * rect.height() * value.clamp(-1.0, 1.0);
+                egui::pos2(x, y)
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+        ));
+    }
+
+    fn spectrum(&mut self, painter: &egui::Painter, rect: egui::Rect, ring: &SampleRing) {
+        let samples = &mut self.samples[..FFT_SIZE];
+        ring.read_latest(samples);
+
+        // hann window
+        for (index, (real, imag)) in self
+            .fft_real
+            .iter_mut()
+            .zip(self.fft_imag.iter_mut())
+            .enumerate()
+        {
+            let window = 0.5 - 0.5 * (TAU * index as f32 / FFT_SIZE as f32).cos();
+            *real = window * samples[index];
+            *imag = 0.0;
+        }
+        fft(&mut self.fft_real, &mut self.fft_imag);
+
+        let sample_rate = ring.sample_rate();
+        let log_range = (SPECTRUM_MAX_FREQUENCY / SPECTRUM_MIN_FREQUENCY).ln();
+        let to_x = |frequency: f32| {
+            rect.left() + rect.width() * (frequency / SPECTRUM_MIN_FREQUENCY).ln() / log_range
+        };
+
+        // frequency grid
+        for frequency in [100.0, 1000.0, 10000.0] {
+            let x = to_x(frequency);
+            painter.line_segment(
+                [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+                egui::Stroke::new(1.0, egui::Color32::from_gray(60)),
+            );
+            painter.text(
+                egui::pos2(x + 2.0, rect.bottom() - 2.0),
+                egui::Align2::LEFT_BOTTOM,
+                if frequency < 1000.0 {
+                    format!("{}", frequency)
+                } else {
+                    format!("{}k", frequency / 1000.0)
+                },
+                egui::FontId::monospace(10.0),
+                egui::Color32::from_gray(120),
+            );
+        }
+
+        // normalize so that full scale sine reads 0 dB (hann window has coherent gain 1/2)
+        let scale = 4.0 / FFT_SIZE as f32;
+        let points: Vec<egui::Pos2> = (1..(FFT_SIZE / 2))
+            .filter_map(|bin| {
+                let frequency = bin as f32 * sample_rate / FFT_SIZE as f32;
+                if !(SPECTRUM_MIN_FREQUENCY..=SPECTRUM_MAX_FREQUENCY).contains(&frequency) {
+                    return None;
+                }
+                let magnitude = scale * self.fft_real[bin].hypot(self.fft_imag[bin]);
+                let db = 20.0 * magnitude.max(1e-9).log10();
+                let t =
+                    ((db - SPECTRUM_MIN_DB) / (SPECTRUM_MAX_DB - SPECTRUM_MIN_DB)).clamp(0.0, 1.0);
+                Some(egui::pos2(
+                    to_x(frequency),
+                    rect.bottom() - t * rect.height(),
+                ))
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE),
+        ));
+    }
+}
+
+impl Default for AnalyzerView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// in-place iterative radix-2 Cooley-Tukey (length must be power of two)
+fn fft(real: &mut [f32], imag: &mut [f32]) {
+    let n = real.len();
+    assert!(n.is_power_of_two() && imag.len() == n);
+
+    // bit-reversal permutation
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if i < j {
+            real.swap(i, j);
+            imag.swap(i, j);
+        }
+    }
+
+    // butterflies
+    let mut size = 2;
+    while size <= n {
+        let half = size / 2;
+        let angle = -TAU / size as f32;
+        for start in (0..n).step_by(size) {
+            for k in 0..half {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + half);
+                let b_real = real[b] * cos - imag[b] * sin;
+                let b_imag = real[b] * sin + imag[b] * cos;
+                real[b] = real[a] - b_real;
+                imag[b] = imag[a] - b_imag;
+                real[a] += b_real;
+                imag[a] += b_imag;
+            }
+        }
+        size *= 2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 1024;
+
+    fn magnitudes(real: &mut [f32]) -> Vec<f32> {
+        let mut imag = vec![0.0; real.len()];
+        fft(real, &mut imag);
+        real.iter()
+            .zip(imag.iter())
+            .map(|(re, im)| re.hypot(*im))
+            .collect()
+    }
+
+    #[test]
+    fn fft_sinusoid() {
+        for bin in [1, 37, 200, SIZE / 2 - 1] {
+            let mut real: Vec<f32> = (0..SIZE)
+                .map(|n| (TAU * (bin * n % SIZE) as f32 / SIZE as f32).cos())
+                .collect();
+            let magnitudes = magnitudes(&mut real);
+            // half of the amplitude at positive and negative frequencies each
+            for (index, magnitude) in magnitudes.iter().enumerate() {
+                let expected = if index == bin || index == SIZE - bin {
+                    SIZE as f32 / 2.0
+                } else {
+                    0.0
+                };
+                assert!(
+                    (magnitude - expected).abs() < 1e-2,
+                    "bin {}: {} at {}",
+                    bin,
+                    magnitude,
+                    index
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fft_dc() {
+        let mut real = vec![0.5; SIZE];
+        let magnitudes = magnitudes(&mut real);
+        assert!((magnitudes[0] - 0.5 * SIZE as f32).abs() < 1e-3);
+        assert!(magnitudes[1..].iter().all(|magnitude| *magnitude < 1e-3));
+    }
+}
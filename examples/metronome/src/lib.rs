@@ -1,11 +1,17 @@
 use nih_plug::prelude::*;
 use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
-use std::{f32::consts::TAU, sync::Arc};
+use std::{
+    f32::consts::TAU,
+    sync::{Arc, Mutex},
+};
+use tuning::{Tuning, TuningTable, TuningView};
 
 pub struct MyPlugin {
     params: Arc<MyParams>,
     sample_phase: f32,
     envelope_phase: f32,
+    // copied from `params.tuning` at the start of each block
+    tuning_table: TuningTable,
 }
 
 #[derive(Params)]
@@ -13,6 +19,10 @@ struct MyParams {
     #[persist = "editor-state"]
     editor_state: Arc<EguiState>,
 
+    // loaded scale/mapping files are saved with the session
+    #[persist = "tuning"]
+    tuning: Arc<Mutex<Tuning>>,
+
     #[id = "play_gain"]
     play_gain: FloatParam, // glacefully play/pause
 
@@ -24,6 +34,9 @@ struct MyParams {
 
     #[id = "note"]
     note: IntParam,
+
+    #[id = "reference_pitch"]
+    reference_pitch: FloatParam,
 }
 
 impl Default for MyPlugin {
@@ -32,6 +45,7 @@ impl Default for MyPlugin {
             params: Arc::new(MyParams::default()),
             sample_phase: 0.0,
             envelope_phase: 0.0,
+            tuning_table: TuningTable::default(),
         }
     }
 }
@@ -39,7 +53,9 @@ impl Default for MyPlugin {
 impl Default for MyParams {
     fn default() -> Self {
         Self {
-            editor_state: EguiState::from_size(300, 120),
+            editor_state: EguiState::from_size(300, 170),
+
+            tuning: Arc::new(Mutex::new(Tuning::default())),
 
             play_gain: FloatParam::new("Play", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
                 .with_smoother(SmoothingStyle::Linear(50.0)),
@@ -72,6 +88,8 @@ impl Default for MyParams {
             )
             .with_value_to_string(formatters::v2s_i32_note_formatter())
             .with_string_to_value(formatters::s2v_i32_note_formatter()),
+
+            reference_pitch: tuning::reference_pitch_param(440.0),
         }
     }
 }
@@ -99,10 +117,10 @@ impl Plugin for MyPlugin {
         let params = self.params.clone();
         create_egui_editor(
             params.editor_state.clone(),
-            (),
+            TuningView::new(),
             |_, _| {},
             // TODO: why not `&mut egui_ctx` (e.g. for `egui_ctx.input().consume_key()`) ? https://github.com/BillyDM/egui-baseview/blob/d2512c25bff19c05d73032e5349f3acb03d5da25/src/window.rs#L296
-            move |egui_ctx, setter, _state| {
+            move |egui_ctx, setter, tuning_view| {
                 egui::CentralPanel::default().show(egui_ctx, |ui| {
                     egui::Grid::new("params")
                         .num_columns(2)
@@ -119,6 +137,8 @@ impl Plugin for MyPlugin {
                             ui.label("Note");
                             ui.add(widgets::ParamSlider::for_param(&params.note, setter));
                             ui.end_row();
+
+                            tuning_view.ui(ui, &params.tuning, &params.reference_pitch, setter);
                         });
 
                     let play_gain = params.play_gain.value();
@@ -147,7 +167,12 @@ impl Plugin for MyPlugin {
         // params
         let bpm = self.params.bpm.value() as f32;
         let note = self.params.note.value() as u8;
-        let note_freq = nih_plug::util::midi_note_to_freq(note);
+        Tuning::try_update(&self.params.tuning, &mut self.tuning_table);
+        // silent if the note is unmapped by the keyboard mapping
+        let note_freq = self
+            .tuning_table
+            .frequency(note, self.params.reference_pitch.value())
+            .unwrap_or(0.0);
 
         for samples in buffer.iter_samples() {
             let play_gain = self.params.play_gain.smoothed.next();
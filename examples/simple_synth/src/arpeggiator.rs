@@ -0,0 +1,336 @@
+use crate::{
+    combo_box_for_enum_param, modulation::SyncDivision, param_checkbox, Random, TransportInfo,
+};
+use nih_plug::prelude::*;
+use nih_plug_egui::{egui, widgets};
+
+// sits between incoming notes (midi and Play button) and `VoicePool`.
+// while enabled, held notes are collected here and played one at a time on the tempo grid.
+
+const MAX_NOTES: usize = 16;
+const MAX_OCTAVES: usize = 4;
+
+//
+// ArpMode
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum ArpMode {
+    Up,
+    Down,
+    // without repeating the top/bottom notes
+    #[name = "Up-Down"]
+    UpDown,
+    Random,
+    #[name = "As Played"]
+    AsPlayed,
+}
+
+//
+// ArpeggiatorParams
+//
+
+#[derive(Params)]
+pub(crate) struct ArpeggiatorParams {
+    #[id = "arp_enabled"]
+    pub(crate) enabled: BoolParam,
+
+    #[id = "arp_mode"]
+    pub(crate) mode: EnumParam<ArpMode>,
+
+    #[id = "arp_octaves"]
+    pub(crate) octaves: IntParam,
+
+    #[id = "arp_division"]
+    pub(crate) division: EnumParam<SyncDivision>,
+
+    // note length relative to step
+    #[id = "arp_gate"]
+    pub(crate) gate: FloatParam,
+
+    // keep playing released notes until next note-on
+    #[id = "arp_latch"]
+    pub(crate) latch: BoolParam,
+}
+
+impl ArpeggiatorParams {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: BoolParam::new("Arpeggiator", false),
+
+            mode: EnumParam::new("Arp Mode", ArpMode::Up),
+
+            octaves: IntParam::new(
+                "Arp Octaves",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_OCTAVES as i32,
+                },
+            )
+            .with_unit(" oct"),
+
+            division: EnumParam::new("Arp Rate", SyncDivision::Sixteenth),
+
+            gate: FloatParam::new(
+                "Arp Gate",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.05,
+                    max: 1.0,
+                },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            latch: BoolParam::new("Arp Latch", false),
+        }
+    }
+}
+
+//
+// gui (rows of 2-column grid)
+//
+
+pub(crate) fn arpeggiator_ui(ui: &mut egui::Ui, params: &ArpeggiatorParams, setter: &ParamSetter) {
+    ui.label("Arpeggiator");
+    ui.horizontal(|ui| {
+        param_checkbox(ui, &params.enabled, "On", setter);
+        param_checkbox(ui, &params.latch, "Latch", setter);
+    });
+    ui.end_row();
+
+    ui.label("Mode");
+    combo_box_for_enum_param(
+        egui::ComboBox::from_id_source("arp_mode"),
+        ui,
+        &params.mode,
+        setter,
+    );
+    ui.end_row();
+
+    ui.label("Rate");
+    combo_box_for_enum_param(
+        egui::ComboBox::from_id_source("arp_division"),
+        ui,
+        &params.division,
+        setter,
+    );
+    ui.end_row();
+
+    ui.label("Octaves");
+    ui.add(widgets::ParamSlider::for_param(&params.octaves, setter));
+    ui.end_row();
+
+    ui.label("Gate");
+    ui.add(widgets::ParamSlider::for_param(&params.gate, setter));
+    ui.end_row();
+}
+
+//
+// Arpeggiator
+//
+
+#[derive(Debug, Clone, Copy)]
+struct ArpNote {
+    note: u8,
+    velocity: f32,
+    // false when released but kept by latch
+    held: bool,
+}
+
+// notes to be sent to `VoicePool` (note-off first)
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ArpEvents {
+    pub(crate) note_off: Option<u8>,
+    pub(crate) note_on: Option<(u8, f32)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Arpeggiator {
+    pub(crate) enabled: bool,
+    mode: ArpMode,
+    octaves: usize,
+    latch: bool,
+    // in beats
+    step_length: f64,
+    gate: f64,
+    // beats per sample
+    beat_delta: f64,
+    // follows host transport while playing, otherwise free-running
+    position: f64,
+    // in played order (fixed size so that audio thread doesn't allocate)
+    notes: [ArpNote; MAX_NOTES],
+    num_notes: usize,
+    step: usize,
+    // grid index of the current step (`None` to start immediately)
+    step_index: Option<i64>,
+    // sounding note and its remaining length in beats
+    current: Option<u8>,
+    gate_remaining: f64,
+    random: Random,
+}
+
+impl Arpeggiator {
+    pub(crate) fn new() -> Self {
+        Self {
+            enabled: false,
+            mode: ArpMode::Up,
+            octaves: 1,
+            latch: false,
+            step_length: 0.25,
+            gate: 0.5,
+            beat_delta: 0.0,
+            position: 0.0,
+            notes: [ArpNote {
+                note: 0,
+                velocity: 0.0,
+                held: false,
+            }; MAX_NOTES],
+            num_notes: 0,
+            step: 0,
+            step_index: None,
+            current: None,
+            gate_remaining: 0.0,
+            random: Random::new(0x5EED),
+        }
+    }
+
+    // forget all notes (sounding note is released on next sample)
+    pub(crate) fn reset(&mut self) {
+        self.num_notes = 0;
+    }
+
+    pub(crate) fn update(&mut self, params: &ArpeggiatorParams, transport: &TransportInfo) {
+        self.enabled = params.enabled.value();
+        self.mode = params.mode.value();
+        self.octaves = params.octaves.value() as usize;
+        self.step_length = params.division.value().beats();
+        self.gate = params.gate.value() as f64;
+
+        // drop released notes when latch is turned off
+        let latch = params.latch.value();
+        if self.latch && !latch {
+            self.retain_held();
+        }
+        self.latch = latch;
+
+        let tempo = transport.tempo.unwrap_or(120.0);
+        self.beat_delta = tempo / 60.0 / transport.sample_rate as f64;
+        if let Some(pos_beats) = transport.pos_beats {
+            self.position = pos_beats;
+        }
+    }
+
+    pub(crate) fn note_on(&mut self, note: u8, velocity: f32) {
+        // new chord replaces latched notes
+        if self.latch && !self.notes[..self.num_notes].iter().any(|n| n.held) {
+            self.num_notes = 0;
+        }
+        if self.num_notes == 0 {
+            self.step = 0;
+            self.step_index = None;
+        }
+        if let Some(existing) = self.notes[..self.num_notes]
+            .iter_mut()
+            .find(|n| n.note == note)
+        {
+            existing.velocity = velocity;
+            existing.held = true;
+        } else if self.num_notes < MAX_NOTES {
+            self.notes[self.num_notes] = ArpNote {
+                note,
+                velocity,
+                held: true,
+            };
+            self.num_notes += 1;
+        }
+    }
+
+    pub(crate) fn note_off(&mut self, note: u8) {
+        for n in self.notes[..self.num_notes].iter_mut() {
+            if n.note == note {
+                n.held = false;
+            }
+        }
+        if !self.latch {
+            self.retain_held();
+        }
+    }
+
+    fn retain_held(&mut self) {
+        let mut len = 0;
+        for index in 0..self.num_notes {
+            if self.notes[index].held {
+                self.notes[len] = self.notes[index];
+                len += 1;
+            }
+        }
+        self.num_notes = len;
+    }
+
+    pub(crate) fn next(&mut self) -> ArpEvents {
+        let mut events = ArpEvents::default();
+
+        if self.num_notes == 0 {
+            events.note_off = self.current.take();
+            self.position += self.beat_delta;
+            return events;
+        }
+
+        let step_index = (self.position / self.step_length).floor() as i64;
+        if self.step_index != Some(step_index) {
+            self.step_index = Some(step_index);
+            events.note_off = self.current.take();
+            let (note, velocity) = self.next_note();
+            events.note_on = Some((note, velocity));
+            self.current = Some(note);
+            self.gate_remaining = self.gate * self.step_length;
+        } else if self.current.is_some() && self.gate_remaining <= 0.0 {
+            events.note_off = self.current.take();
+        }
+
+        self.gate_remaining -= self.beat_delta;
+        self.position += self.beat_delta;
+        events
+    }
+
+    fn next_note(&mut self) -> (u8, f32) {
+        // notes in pattern order repeated over octaves
+        let mut notes = self.notes;
+        let notes = &mut notes[..self.num_notes];
+        if self.mode != ArpMode::AsPlayed {
+            notes.sort_unstable_by_key(|n| n.note);
+        }
+        let mut sequence = [(0u8, 0.0f32); MAX_NOTES * MAX_OCTAVES];
+        let mut len = 0;
+        for octave in 0..self.octaves {
+            for n in notes.iter() {
+                let note = n.note as usize + 12 * octave;
+                if note <= 127 {
+                    sequence[len] = (note as u8, n.velocity);
+                    len += 1;
+                }
+            }
+        }
+        let len = len.max(1);
+
+        let index = match self.mode {
+            ArpMode::Up | ArpMode::AsPlayed => self.step % len,
+            ArpMode::Down => len - 1 - self.step % len,
+            ArpMode::UpDown if len > 1 => {
+                let index = self.step % (2 * len - 2);
+                if index < len {
+                    index
+                } else {
+                    2 * len - 2 - index
+                }
+            }
+            ArpMode::UpDown => 0,
+            ArpMode::Random => (self.random.next_f32() * len as f32) as usize % len,
+        };
+        self.step = self.step.wrapping_add(1);
+        sequence[index]
+    }
+}
@@ -0,0 +1,622 @@
+use crate::{
+    combo_box_for_enum_param, modulation::SyncDivision, param_checkbox, s2v_f32_scale,
+    v2s_f32_scale, TransportInfo,
+};
+use nih_plug::prelude::*;
+use nih_plug_egui::{egui, widgets};
+use std::f32::consts::TAU;
+
+// post-voice effects applied to the sum of voices in fixed order
+//
+//   voices -> chorus -> delay -> reverb -> width -> gain
+//
+// so that the chorus widens the dry signal before it's repeated by the delay,
+// and the reverb diffuses both the chorus and the delay taps.
+// each effect crossfades between its input and its output by `mix`,
+// and a bypassed effect passes its input through unchanged.
+
+//
+// EffectsParams
+//
+
+#[derive(Params)]
+pub(crate) struct EffectsParams {
+    #[id = "chorus_bypass"]
+    pub(crate) chorus_bypass: BoolParam,
+
+    #[id = "chorus_rate"]
+    pub(crate) chorus_rate: FloatParam,
+
+    #[id = "chorus_depth"]
+    pub(crate) chorus_depth: FloatParam,
+
+    #[id = "chorus_mix"]
+    pub(crate) chorus_mix: FloatParam,
+
+    #[id = "delay_bypass"]
+    pub(crate) delay_bypass: BoolParam,
+
+    #[id = "delay_time"]
+    pub(crate) delay_time: FloatParam,
+
+    #[id = "delay_sync"]
+    pub(crate) delay_sync: BoolParam,
+
+    #[id = "delay_division"]
+    pub(crate) delay_division: EnumParam<SyncDivision>,
+
+    #[id = "delay_feedback"]
+    pub(crate) delay_feedback: FloatParam,
+
+    #[id = "delay_mix"]
+    pub(crate) delay_mix: FloatParam,
+
+    #[id = "reverb_bypass"]
+    pub(crate) reverb_bypass: BoolParam,
+
+    #[id = "reverb_size"]
+    pub(crate) reverb_size: FloatParam,
+
+    #[id = "reverb_damping"]
+    pub(crate) reverb_damping: FloatParam,
+
+    #[id = "reverb_mix"]
+    pub(crate) reverb_mix: FloatParam,
+}
+
+impl EffectsParams {
+    // all bypassed by default so that the output stays dry unless asked
+    pub(crate) fn new() -> Self {
+        Self {
+            chorus_bypass: BoolParam::new("Chorus Bypass", true),
+
+            chorus_rate: FloatParam::new(
+                "Chorus Rate",
+                0.8,
+                FloatRange::Skewed {
+                    min: 0.05,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            chorus_depth: percentage_param("Chorus Depth", 0.5),
+
+            chorus_mix: percentage_param("Chorus Mix", 0.5),
+
+            delay_bypass: BoolParam::new("Delay Bypass", true),
+
+            delay_time: FloatParam::new(
+                "Delay Time",
+                0.375,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: MAX_DELAY_TIME,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            delay_sync: BoolParam::new("Delay Sync", false),
+
+            delay_division: EnumParam::new("Delay Division", SyncDivision::Eighth),
+
+            delay_feedback: FloatParam::new(
+                "Delay Feedback",
+                0.4,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: MAX_DELAY_FEEDBACK,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            delay_mix: percentage_param("Delay Mix", 0.3),
+
+            reverb_bypass: BoolParam::new("Reverb Bypass", true),
+
+            reverb_size: percentage_param("Reverb Size", 0.5),
+
+            reverb_damping: percentage_param("Reverb Damping", 0.5),
+
+            reverb_mix: percentage_param("Reverb Mix", 0.25),
+        }
+    }
+}
+
+fn percentage_param(name: &'static str, default: f32) -> FloatParam {
+    FloatParam::new(name, default, FloatRange::Linear { min: 0.0, max: 1.0 })
+        .with_smoother(SmoothingStyle::Linear(20.0))
+        .with_unit("%")
+        .with_value_to_string(formatters::v2s_f32_percentage(0))
+        .with_string_to_value(formatters::s2v_f32_percentage())
+}
+
+//
+// gui (rows of 2-column grid)
+//
+
+pub(crate) fn effects_ui(ui: &mut egui::Ui, params: &EffectsParams, setter: &ParamSetter) {
+    ui.label("Chorus");
+    param_checkbox(ui, &params.chorus_bypass, "Bypass", setter);
+    ui.end_row();
+
+    ui.label("Rate");
+    ui.add(widgets::ParamSlider::for_param(&params.chorus_rate, setter));
+    ui.end_row();
+
+    ui.label("Depth");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.chorus_depth,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Mix");
+    ui.add(widgets::ParamSlider::for_param(&params.chorus_mix, setter));
+    ui.end_row();
+
+    ui.label("Delay");
+    param_checkbox(ui, &params.delay_bypass, "Bypass", setter);
+    ui.end_row();
+
+    ui.label("Time");
+    ui.horizontal(|ui| {
+        param_checkbox(ui, &params.delay_sync, "Sync", setter);
+        if params.delay_sync.value() {
+            combo_box_for_enum_param(
+                egui::ComboBox::from_id_source("delay_division"),
+                ui,
+                &params.delay_division,
+                setter,
+            );
+        } else {
+            ui.add(widgets::ParamSlider::for_param(&params.delay_time, setter));
+        }
+    });
+    ui.end_row();
+
+    ui.label("Feedback");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.delay_feedback,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Mix");
+    ui.add(widgets::ParamSlider::for_param(&params.delay_mix, setter));
+    ui.end_row();
+
+    ui.label("Reverb");
+    param_checkbox(ui, &params.reverb_bypass, "Bypass", setter);
+    ui.end_row();
+
+    ui.label("Size");
+    ui.add(widgets::ParamSlider::for_param(&params.reverb_size, setter));
+    ui.end_row();
+
+    ui.label("Damping");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.reverb_damping,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Mix");
+    ui.add(widgets::ParamSlider::for_param(&params.reverb_mix, setter));
+    ui.end_row();
+}
+
+//
+// Effects
+//
+
+#[derive(Debug)]
+pub(crate) struct Effects {
+    chorus: Chorus,
+    delay: Delay,
+    reverb: Reverb,
+    chorus_bypass: bool,
+    delay_bypass: bool,
+    reverb_bypass: bool,
+}
+
+impl Effects {
+    pub(crate) fn new() -> Self {
+        Self {
+            chorus: Chorus::new(),
+            delay: Delay::new(),
+            reverb: Reverb::new(),
+            chorus_bypass: true,
+            delay_bypass: true,
+            reverb_bypass: true,
+        }
+    }
+
+    // allocate delay lines for the sample rate (not on audio thread)
+    pub(crate) fn initialize(&mut self, sample_rate: f32) {
+        self.chorus.initialize(sample_rate);
+        self.delay.initialize(sample_rate);
+        self.reverb.initialize(sample_rate);
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.chorus.reset();
+        self.delay.reset();
+        self.reverb.reset();
+    }
+
+    pub(crate) fn update(&mut self, params: &EffectsParams, transport: &TransportInfo) {
+        // clear stale tail when an effect is turned back on
+        let chorus_bypass = params.chorus_bypass.value();
+        if self.chorus_bypass && !chorus_bypass {
+            self.chorus.reset();
+        }
+        self.chorus_bypass = chorus_bypass;
+
+        let delay_bypass = params.delay_bypass.value();
+        if self.delay_bypass && !delay_bypass {
+            self.delay.reset();
+        }
+        self.delay_bypass = delay_bypass;
+
+        let reverb_bypass = params.reverb_bypass.value();
+        if self.reverb_bypass && !reverb_bypass {
+            self.reverb.reset();
+        }
+        self.reverb_bypass = reverb_bypass;
+
+        self.chorus.rate = params.chorus_rate.value();
+        self.delay.time = if params.delay_sync.value() {
+            let beats = params.delay_division.value().beats();
+            let tempo = transport.tempo.unwrap_or(120.0);
+            (beats * 60.0 / tempo) as f32
+        } else {
+            params.delay_time.value()
+        };
+        self.reverb.set_size(params.reverb_size.value());
+        self.reverb.set_damping(params.reverb_damping.value());
+    }
+
+    pub(crate) fn next(
+        &mut self,
+        params: &EffectsParams,
+        input: (f32, f32),
+        delta: f32,
+    ) -> (f32, f32) {
+        // advance smoothers even while bypassed so that they don't jump on resume
+        let chorus_depth = params.chorus_depth.smoothed.next();
+        let chorus_mix = params.chorus_mix.smoothed.next();
+        let delay_feedback = params.delay_feedback.smoothed.next();
+        let delay_mix = params.delay_mix.smoothed.next();
+        let reverb_mix = params.reverb_mix.smoothed.next();
+
+        let mut output = input;
+        if !self.chorus_bypass {
+            let wet = self.chorus.next(output, chorus_depth, delta);
+            output = mix(output, wet, chorus_mix);
+        }
+        if !self.delay_bypass {
+            let wet = self.delay.next(output, delay_feedback);
+            output = mix(output, wet, delay_mix);
+        }
+        if !self.reverb_bypass {
+            let wet = self.reverb.next(output);
+            output = mix(output, wet, reverb_mix);
+        }
+        output
+    }
+}
+
+fn mix(dry: (f32, f32), wet: (f32, f32), mix: f32) -> (f32, f32) {
+    (dry.0 + mix * (wet.0 - dry.0), dry.1 + mix * (wet.1 - dry.1))
+}
+
+//
+// DelayLine
+//
+
+#[derive(Debug)]
+struct DelayLine {
+    buffer: Vec<f32>,
+    position: usize,
+}
+
+impl DelayLine {
+    fn new() -> Self {
+        Self {
+            buffer: vec![],
+            position: 0,
+        }
+    }
+
+    fn initialize(&mut self, length: usize) {
+        self.buffer = vec![0.0; length.max(1)];
+        self.position = 0;
+    }
+
+    fn reset(&mut self) {
+        self.buffer.fill(0.0);
+    }
+
+    fn write(&mut self, value: f32) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        self.buffer[self.position] = value;
+        self.position = (self.position + 1) % self.buffer.len();
+    }
+
+    // fractional delay in samples with linear interpolation (1 is the last written sample)
+    fn read(&self, delay: f32) -> f32 {
+        let length = self.buffer.len();
+        if length == 0 {
+            return 0.0;
+        }
+        let delay = delay.clamp(1.0, length as f32);
+        let whole = delay.floor() as usize;
+        let fraction = delay - whole as f32;
+        let a = self.buffer[(self.position + length - whole) % length];
+        let b = self.buffer[(self.position + 2 * length - whole - 1) % length];
+        a + fraction * (b - a)
+    }
+
+    // fixed delay of the whole buffer length
+    fn tap(&self) -> f32 {
+        self.buffer.get(self.position).copied().unwrap_or(0.0)
+    }
+}
+
+//
+// Chorus
+//
+
+const CHORUS_BASE_MS: f32 = 12.0;
+// modulation at 100% depth
+const CHORUS_DEPTH_MS: f32 = 8.0;
+
+// single modulated delay per channel with quadrature lfo for stereo width
+#[derive(Debug)]
+struct Chorus {
+    rate: f32,
+    phase: f32,
+    lines: [DelayLine; 2],
+    sample_rate: f32,
+}
+
+impl Chorus {
+    fn new() -> Self {
+        Self {
+            rate: 0.8,
+            phase: 0.0,
+            lines: [DelayLine::new(), DelayLine::new()],
+            sample_rate: 44100.0,
+        }
+    }
+
+    fn initialize(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        let length =
+            ((CHORUS_BASE_MS + CHORUS_DEPTH_MS) / 1000.0 * sample_rate).ceil() as usize + 2;
+        for line in self.lines.iter_mut() {
+            line.initialize(length);
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.reset();
+        }
+    }
+
+    fn next(&mut self, input: (f32, f32), depth: f32, delta: f32) -> (f32, f32) {
+        let mut output = [0.0; 2];
+        for (channel, (line, input)) in self.lines.iter_mut().zip([input.0, input.1]).enumerate() {
+            let lfo = (TAU * (self.phase + 0.25 * channel as f32)).sin();
+            let delay_ms = CHORUS_BASE_MS + depth * CHORUS_DEPTH_MS * 0.5 * (lfo + 1.0);
+            line.write(input);
+            output[channel] = line.read(delay_ms / 1000.0 * self.sample_rate);
+        }
+        self.phase = (self.phase + self.rate * delta).fract();
+        (output[0], output[1])
+    }
+}
+
+//
+// Delay
+//
+
+const MAX_DELAY_TIME: f32 = 2.0;
+// synced delay can be longer than `MAX_DELAY_TIME` (e.g. 4/1 at slow tempo) and gets clamped at this
+const MAX_SYNCED_DELAY_TIME: f32 = 8.0;
+const MAX_DELAY_FEEDBACK: f32 = 0.95;
+// glide to new delay time instead of jumping (which clicks)
+const DELAY_GLIDE_MS: f32 = 50.0;
+
+// stereo feedback delay
+#[derive(Debug)]
+struct Delay {
+    // seconds
+    time: f32,
+    // samples (follows `time`)
+    current: f32,
+    glide_weight: f32,
+    lines: [DelayLine; 2],
+    sample_rate: f32,
+}
+
+impl Delay {
+    fn new() -> Self {
+        Self {
+            time: 0.375,
+            current: 0.0,
+            glide_weight: 0.0,
+            lines: [DelayLine::new(), DelayLine::new()],
+            sample_rate: 44100.0,
+        }
+    }
+
+    fn initialize(&mut self, sample_rate: f32) {
+        self.sample_rate = sample_rate;
+        self.glide_weight = (-1.0 / (DELAY_GLIDE_MS / 1000.0 * sample_rate)).exp();
+        let length = (MAX_SYNCED_DELAY_TIME * sample_rate).ceil() as usize + 2;
+        for line in self.lines.iter_mut() {
+            line.initialize(length);
+        }
+        self.current = self.time * sample_rate;
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.reset();
+        }
+        self.current = self.time.min(MAX_SYNCED_DELAY_TIME) * self.sample_rate;
+    }
+
+    fn next(&mut self, input: (f32, f32), feedback: f32) -> (f32, f32) {
+        let target = self.time.min(MAX_SYNCED_DELAY_TIME) * self.sample_rate;
+        self.current = target + self.glide_weight * (self.current - target);
+        let mut output = [0.0; 2];
+        for (channel, (line, input)) in self.lines.iter_mut().zip([input.0, input.1]).enumerate() {
+            let delayed = line.read(self.current);
+            line.write(input + feedback * delayed);
+            output[channel] = delayed;
+        }
+        (output[0], output[1])
+    }
+}
+
+//
+// Reverb
+//
+
+// Schroeder-Moorer reverb with Jezar's "Freeverb" tuning
+// (cf. https://ccrma.stanford.edu/~jos/pasp/Freeverb.html)
+
+// delay lengths in samples at 44.1kHz
+const COMB_LENGTHS: [usize; 8] = [1116, 1188, 1277, 1356, 1422, 1491, 1557, 1617];
+const ALLPASS_LENGTHS: [usize; 4] = [556, 441, 341, 225];
+// right channel is detuned by this for decorrelation
+const STEREO_SPREAD: usize = 23;
+const ALLPASS_FEEDBACK: f32 = 0.5;
+// combs sum up to large gain
+const REVERB_INPUT_GAIN: f32 = 0.015;
+const REVERB_OUTPUT_GAIN: f32 = 3.0;
+
+#[derive(Debug)]
+struct Comb {
+    line: DelayLine,
+    // one-pole lowpass state in feedback path
+    filter: f32,
+}
+
+impl Comb {
+    fn next(&mut self, input: f32, feedback: f32, damping: f32) -> f32 {
+        let output = self.line.tap();
+        self.filter = output + damping * (self.filter - output);
+        self.line.write(input + feedback * self.filter);
+        output
+    }
+}
+
+#[derive(Debug)]
+struct Allpass {
+    line: DelayLine,
+}
+
+impl Allpass {
+    fn next(&mut self, input: f32) -> f32 {
+        let delayed = self.line.tap();
+        self.line.write(input + ALLPASS_FEEDBACK * delayed);
+        delayed - input
+    }
+}
+
+#[derive(Debug)]
+struct Reverb {
+    feedback: f32,
+    damping: f32,
+    combs: [[Comb; 8]; 2],
+    allpasses: [[Allpass; 4]; 2],
+}
+
+impl Reverb {
+    fn new() -> Self {
+        Self {
+            feedback: 0.84,
+            damping: 0.2,
+            combs: std::array::from_fn(|_| {
+                std::array::from_fn(|_| Comb {
+                    line: DelayLine::new(),
+                    filter: 0.0,
+                })
+            }),
+            allpasses: std::array::from_fn(|_| {
+                std::array::from_fn(|_| Allpass {
+                    line: DelayLine::new(),
+                })
+            }),
+        }
+    }
+
+    fn initialize(&mut self, sample_rate: f32) {
+        let scale = |length: usize, channel: usize| {
+            ((length + channel * STEREO_SPREAD) as f32 * sample_rate / 44100.0).round() as usize
+        };
+        for (channel, combs) in self.combs.iter_mut().enumerate() {
+            for (comb, length) in combs.iter_mut().zip(COMB_LENGTHS) {
+                comb.line.initialize(scale(length, channel));
+                comb.filter = 0.0;
+            }
+        }
+        for (channel, allpasses) in self.allpasses.iter_mut().enumerate() {
+            for (allpass, length) in allpasses.iter_mut().zip(ALLPASS_LENGTHS) {
+                allpass.line.initialize(scale(length, channel));
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for comb in self.combs.iter_mut().flatten() {
+            comb.line.reset();
+            comb.filter = 0.0;
+        }
+        for allpass in self.allpasses.iter_mut().flatten() {
+            allpass.line.reset();
+        }
+    }
+
+    // 0..1 to decay time (feedback 0.7..0.98)
+    fn set_size(&mut self, size: f32) {
+        self.feedback = 0.7 + 0.28 * size;
+    }
+
+    // 0..1 to high frequency absorption
+    fn set_damping(&mut self, damping: f32) {
+        self.damping = 0.4 * damping;
+    }
+
+    fn next(&mut self, input: (f32, f32)) -> (f32, f32) {
+        // both channels are fed by mono input and decorrelated by different delay lengths
+        let input = REVERB_INPUT_GAIN * (input.0 + input.1);
+        let mut output = [0.0; 2];
+        for (channel, output) in output.iter_mut().enumerate() {
+            let mut value = 0.0;
+            for comb in self.combs[channel].iter_mut() {
+                value += comb.next(input, self.feedback, self.damping);
+            }
+            for allpass in self.allpasses[channel].iter_mut() {
+                value = allpass.next(value);
+            }
+            *output = REVERB_OUTPUT_GAIN * value;
+        }
+        (output[0], output[1])
+    }
+}
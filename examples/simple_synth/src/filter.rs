@@ -0,0 +1,75 @@
+use nih_plug::prelude::*;
+use std::f32::consts::PI;
+
+//
+// FilterType
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum FilterType {
+    #[name = "Low-pass"]
+    LowPass,
+    #[name = "High-pass"]
+    HighPass,
+    #[name = "Band-pass"]
+    BandPass,
+    Notch,
+}
+
+//
+// Filter
+//
+
+// state variable filter discretized by trapezoidal integration, which stays stable under fast cutoff modulation
+// (cf. Andrew Simper, "Linear Trapezoidal Integrated State Variable Filter With Low Noise Optimisation")
+#[derive(Debug)]
+pub(crate) struct Filter {
+    pub(crate) filter_type: FilterType,
+    // 0 (no resonance) .. 1 (self-oscillation edge)
+    pub(crate) resonance: f32,
+    ic1eq: f32,
+    ic2eq: f32,
+}
+
+impl Filter {
+    pub(crate) fn new() -> Self {
+        Self {
+            filter_type: FilterType::LowPass,
+            resonance: 0.0,
+            ic1eq: 0.0,
+            ic2eq: 0.0,
+        }
+    }
+
+    pub(crate) fn reset(&mut self) {
+        self.ic1eq = 0.0;
+        self.ic2eq = 0.0;
+    }
+
+    pub(crate) fn next(&mut self, input: f32, cutoff: f32, delta: f32) -> f32 {
+        // keep cutoff below nyquist where `tan` blows up
+        let cutoff = cutoff.clamp(10.0, 0.49 * delta.recip());
+        let g = (PI * cutoff * delta).tan();
+        let k = 2.0 * (1.0 - 0.98 * self.resonance.clamp(0.0, 1.0));
+
+        let a1 = 1.0 / (1.0 + g * (g + k));
+        let a2 = g * a1;
+        let a3 = g * a2;
+        let v3 = input - self.ic2eq;
+        let v1 = a1 * self.ic1eq + a2 * v3;
+        let v2 = self.ic2eq + a2 * self.ic1eq + a3 * v3;
+        self.ic1eq = 2.0 * v1 - self.ic1eq;
+        self.ic2eq = 2.0 * v2 - self.ic2eq;
+
+        let low = v2;
+        let band = v1;
+        let high = input - k * v1 - v2;
+        match self.filter_type {
+            FilterType::LowPass => low,
+            FilterType::HighPass => high,
+            // normalize to unity gain at cutoff
+            FilterType::BandPass => k * band,
+            FilterType::Notch => low + high,
+        }
+    }
+}
@@ -1,17 +1,58 @@
+use analyzer::{AnalyzerView, SampleRing};
+use arpeggiator::{arpeggiator_ui, Arpeggiator, ArpeggiatorParams};
+use effects::{effects_ui, Effects, EffectsParams};
+use filter::FilterType;
+use meter::{Meter, MeterLevels};
+use modulation::{Lfo, LfoParams, ModSlot, ModSlotParams, NUM_LFOS, NUM_MOD_SLOTS};
 use nih_plug::prelude::*;
 use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
+use noise::NoiseType;
 use std::{
-    f32::consts::TAU,
+    f32::consts::{FRAC_PI_4, SQRT_2, TAU},
     sync::{
         atomic::{AtomicIsize, Ordering},
-        Arc,
+        Arc, Mutex,
     },
 };
+use tuning::{Tuning, TuningTable, TuningView};
+use voice::{
+    Glide, GlideMode, GlideType, PressureDestination, RetriggerMode, SynthesisMode, VoicePool,
+    CONTROLLER_SMOOTHING, MAX_POLYPHONY, MAX_UNISON,
+};
+use wavetable::{Wavetable, WavetableBank};
+
+mod arpeggiator;
+mod effects;
+mod filter;
+mod meter;
+mod modulation;
+mod noise;
+pub mod render;
+mod voice;
+mod wavetable;
 
 pub struct MyPlugin {
     params: Arc<MyParams>,
-    oscillator: Oscillator,
-    envelop: Envelope,
+    voice_pool: VoicePool,
+    lfos: [Lfo; NUM_LFOS],
+    mod_slots: [ModSlot; NUM_MOD_SLOTS],
+    meter: Meter,
+    meter_levels: Arc<MeterLevels>,
+    analyzer_ring: Arc<SampleRing>,
+    wavetable_bank: Arc<WavetableBank>,
+    // table used by audio thread (updated from `wavetable_bank` at the start of each block)
+    wavetable: Arc<Wavetable>,
+    // note triggered by Play button (kept to release the same note even if `note` param changes)
+    ui_note: Option<u8>,
+    // midi controllers (pitch bend in [-1, 1], others in [0, 1])
+    pitch_bend: Smoother<f32>,
+    mod_wheel: Smoother<f32>,
+    channel_pressure: Smoother<f32>,
+    vibrato: Oscillator,
+    effects: Effects,
+    arpeggiator: Arpeggiator,
+    // copied from `params.tuning` at the start of each block
+    tuning_table: TuningTable,
 }
 
 #[derive(Params)]
@@ -19,30 +60,194 @@ struct MyParams {
     #[persist = "editor-state"]
     editor_state: Arc<EguiState>,
 
+    // loaded scale/mapping files are saved with the session
+    #[persist = "tuning"]
+    tuning: Arc<Mutex<Tuning>>,
+
+    #[id = "reference_pitch"]
+    reference_pitch: FloatParam,
+
     #[id = "gain"]
     gain: FloatParam,
 
+    #[id = "pan"]
+    pan: FloatParam,
+
+    #[id = "pan_spread"]
+    pan_spread: FloatParam,
+
+    #[id = "width"]
+    width: FloatParam,
+
+    #[id = "synthesis"]
+    synthesis: EnumParam<SynthesisMode>,
+
     #[id = "oscillator"]
     oscillator_type: EnumParam<OscillatorType>,
 
+    #[id = "wavetable_position"]
+    wavetable_position: FloatParam,
+
+    #[id = "unison"]
+    unison: IntParam,
+
+    #[id = "unison_detune"]
+    unison_detune: FloatParam,
+
+    #[id = "unison_spread"]
+    unison_spread: FloatParam,
+
+    #[id = "sub_type"]
+    sub_type: EnumParam<OscillatorType>,
+
+    #[id = "sub_octave"]
+    sub_octave: IntParam,
+
+    #[id = "sub_level"]
+    sub_level: FloatParam,
+
+    #[id = "noise_type"]
+    noise_type: EnumParam<NoiseType>,
+
+    #[id = "noise_level"]
+    noise_level: FloatParam,
+
     #[id = "attack"]
     attack: FloatParam,
 
+    #[id = "attack_curve"]
+    attack_curve: FloatParam,
+
+    #[id = "decay"]
+    decay: FloatParam,
+
+    #[id = "decay_curve"]
+    decay_curve: FloatParam,
+
+    #[id = "sustain"]
+    sustain: FloatParam,
+
     #[id = "release"]
     release: FloatParam,
 
+    #[id = "release_curve"]
+    release_curve: FloatParam,
+
+    #[id = "filter_type"]
+    filter_type: EnumParam<FilterType>,
+
+    #[id = "cutoff"]
+    cutoff: FloatParam,
+
+    #[id = "resonance"]
+    resonance: FloatParam,
+
+    #[id = "filter_env_amount"]
+    filter_envelope_amount: FloatParam,
+
+    #[id = "filter_attack"]
+    filter_attack: FloatParam,
+
+    #[id = "filter_decay"]
+    filter_decay: FloatParam,
+
+    #[id = "filter_sustain"]
+    filter_sustain: FloatParam,
+
+    #[id = "filter_release"]
+    filter_release: FloatParam,
+
+    #[id = "fm_ratio"]
+    fm_ratio: FloatParam,
+
+    #[id = "fm_fine"]
+    fm_fine: FloatParam,
+
+    #[id = "fm_index"]
+    fm_index: FloatParam,
+
+    #[id = "fm_attack"]
+    fm_attack: FloatParam,
+
+    #[id = "fm_decay"]
+    fm_decay: FloatParam,
+
+    #[id = "fm_sustain"]
+    fm_sustain: FloatParam,
+
+    #[id = "fm_release"]
+    fm_release: FloatParam,
+
+    #[id = "pitch_bend_range"]
+    pitch_bend_range: IntParam,
+
+    #[id = "vibrato_rate"]
+    vibrato_rate: FloatParam,
+
+    #[id = "vibrato_depth"]
+    vibrato_depth: FloatParam,
+
+    #[id = "pressure_destination"]
+    pressure_destination: EnumParam<PressureDestination>,
+
+    #[id = "pressure_amount"]
+    pressure_amount: FloatParam,
+
+    #[nested(array, group = "LFO")]
+    lfos: [LfoParams; NUM_LFOS],
+
+    #[nested(array, group = "Modulation")]
+    mod_slots: [ModSlotParams; NUM_MOD_SLOTS],
+
+    #[nested(group = "Effects")]
+    effects: EffectsParams,
+
     #[id = "note"]
     note: IntParam,
 
+    #[id = "polyphony"]
+    polyphony: IntParam,
+
+    #[id = "retrigger"]
+    retrigger: EnumParam<RetriggerMode>,
+
+    #[id = "glide_mode"]
+    glide_mode: EnumParam<GlideMode>,
+
+    #[id = "glide_type"]
+    glide_type: EnumParam<GlideType>,
+
+    #[id = "glide_time"]
+    glide_time: FloatParam,
+
+    #[nested(group = "Arpeggiator")]
+    arpeggiator: ArpeggiatorParams,
+
     note_state: Arc<NoteState>,
 }
 
 impl Default for MyPlugin {
     fn default() -> Self {
+        let meter_levels = Arc::new(MeterLevels::new());
+        let wavetable_bank = Arc::new(WavetableBank::new());
         Self {
             params: Arc::new(MyParams::default()),
-            oscillator: Oscillator::new(),
-            envelop: Envelope::new(),
+            voice_pool: VoicePool::new(),
+            lfos: std::array::from_fn(|_| Lfo::new()),
+            mod_slots: [ModSlot::new(); NUM_MOD_SLOTS],
+            meter: Meter::new(meter_levels.clone()),
+            meter_levels,
+            analyzer_ring: Arc::new(SampleRing::new(4096)),
+            wavetable: wavetable_bank.selected(),
+            wavetable_bank,
+            ui_note: None,
+            pitch_bend: Smoother::new(CONTROLLER_SMOOTHING),
+            mod_wheel: Smoother::new(CONTROLLER_SMOOTHING),
+            channel_pressure: Smoother::new(CONTROLLER_SMOOTHING),
+            vibrato: Oscillator::new(),
+            effects: Effects::new(),
+            arpeggiator: Arpeggiator::new(),
+            tuning_table: TuningTable::default(),
         }
     }
 }
@@ -50,7 +255,11 @@ impl Default for MyPlugin {
 impl Default for MyParams {
     fn default() -> Self {
         Self {
-            editor_state: EguiState::from_size(300, 180),
+            editor_state: EguiState::from_size(450, 600),
+
+            tuning: Arc::new(Mutex::new(Tuning::default())),
+
+            reference_pitch: tuning::reference_pitch_param(440.0),
 
             gain: FloatParam::new(
                 "Gain",
@@ -66,8 +275,104 @@ impl Default for MyParams {
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
 
+            // applied to each voice on top of unison spread and modulation
+            pan: FloatParam::new(
+                "Pan",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_panning())
+            .with_string_to_value(formatters::s2v_f32_panning()),
+
+            // each note is placed randomly within this range around `pan`
+            pan_spread: FloatParam::new(
+                "Pan Spread",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            // 0% (mono) .. 100% (unchanged) .. 200% (side doubled)
+            width: FloatParam::new("Width", 1.0, FloatRange::Linear { min: 0.0, max: 2.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            synthesis: EnumParam::new("Synthesis", SynthesisMode::Subtractive),
+
             oscillator_type: EnumParam::new("Oscillator", OscillatorType::Sine),
 
+            wavetable_position: FloatParam::new(
+                "Wavetable Position",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            unison: IntParam::new(
+                "Unison",
+                1,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_UNISON as i32,
+                },
+            ),
+
+            unison_detune: FloatParam::new(
+                "Unison Detune",
+                20.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            unison_spread: FloatParam::new(
+                "Unison Spread",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            sub_type: EnumParam::new("Sub", OscillatorType::Square),
+
+            // octaves below oscillator
+            sub_octave: IntParam::new("Sub Octave", 1, IntRange::Linear { min: 1, max: 2 })
+                .with_unit(" oct"),
+
+            sub_level: FloatParam::new("Sub Level", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            noise_type: EnumParam::new("Noise", NoiseType::White),
+
+            noise_level: FloatParam::new(
+                "Noise Level",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
             attack: FloatParam::new(
                 "Attack",
                 0.001,
@@ -81,6 +386,27 @@ impl Default for MyParams {
             .with_value_to_string(v2s_f32_scale(1000.0, 0))
             .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
 
+            attack_curve: curve_param("Attack Curve", 0.0),
+
+            decay: FloatParam::new(
+                "Decay",
+                0.1,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            decay_curve: curve_param("Decay Curve", 0.0),
+
+            sustain: FloatParam::new("Sustain", 1.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
             release: FloatParam::new(
                 "Release",
                 0.1,
@@ -93,6 +419,210 @@ impl Default for MyParams {
             .with_value_to_string(v2s_f32_scale(1000.0, 0))
             .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
 
+            release_curve: curve_param("Release Curve", 0.0),
+
+            filter_type: EnumParam::new("Filter", FilterType::LowPass),
+
+            cutoff: FloatParam::new(
+                "Cutoff",
+                20000.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(1))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+
+            resonance: FloatParam::new("Resonance", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            filter_envelope_amount: FloatParam::new(
+                "Filter Env Amount",
+                0.0,
+                FloatRange::Linear {
+                    min: -8.0,
+                    max: 8.0,
+                },
+            )
+            .with_unit(" oct")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            filter_attack: FloatParam::new(
+                "Filter Attack",
+                0.001,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            filter_decay: FloatParam::new(
+                "Filter Decay",
+                0.3,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            filter_sustain: FloatParam::new(
+                "Filter Sustain",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            filter_release: FloatParam::new(
+                "Filter Release",
+                0.1,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            fm_ratio: FloatParam::new(
+                "FM Ratio",
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.5,
+                    max: 16.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_step_size(0.5)
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            fm_fine: FloatParam::new(
+                "FM Fine",
+                0.0,
+                FloatRange::Linear {
+                    min: -100.0,
+                    max: 100.0,
+                },
+            )
+            .with_unit(" cents")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+
+            // peak phase deviation of carrier in radians
+            fm_index: FloatParam::new(
+                "FM Index",
+                2.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            fm_attack: FloatParam::new(
+                "FM Attack",
+                0.001,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            fm_decay: FloatParam::new(
+                "FM Decay",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            fm_sustain: FloatParam::new(
+                "FM Sustain",
+                0.2,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            fm_release: FloatParam::new(
+                "FM Release",
+                0.3,
+                FloatRange::Linear {
+                    min: 0.001,
+                    max: 2.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            pitch_bend_range: IntParam::new(
+                "Pitch Bend Range",
+                2,
+                IntRange::Linear { min: 0, max: 24 },
+            )
+            .with_unit(" st"),
+
+            vibrato_rate: FloatParam::new(
+                "Vibrato Rate",
+                5.0,
+                FloatRange::Skewed {
+                    min: 0.1,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            // at full mod wheel
+            vibrato_depth: FloatParam::new(
+                "Vibrato Depth",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 2.0 },
+            )
+            .with_unit(" st")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            pressure_destination: EnumParam::new("Pressure", PressureDestination::Brightness),
+
+            pressure_amount: FloatParam::new(
+                "Pressure Amount",
+                0.5,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+
+            lfos: std::array::from_fn(LfoParams::new),
+
+            mod_slots: std::array::from_fn(ModSlotParams::new),
+
+            effects: EffectsParams::new(),
+
             note: IntParam::new(
                 "Note",
                 // A4
@@ -106,6 +636,36 @@ impl Default for MyParams {
             .with_value_to_string(formatters::v2s_i32_note_formatter())
             .with_string_to_value(formatters::s2v_i32_note_formatter()),
 
+            polyphony: IntParam::new(
+                "Polyphony",
+                8,
+                IntRange::Linear {
+                    min: 1,
+                    max: MAX_POLYPHONY as i32,
+                },
+            ),
+
+            retrigger: EnumParam::new("Retrigger", RetriggerMode::Continue),
+
+            glide_mode: EnumParam::new("Glide", GlideMode::Off),
+
+            glide_type: EnumParam::new("Glide Type", GlideType::ConstantTime),
+
+            glide_time: FloatParam::new(
+                "Glide Time",
+                0.1,
+                FloatRange::Skewed {
+                    min: 0.001,
+                    max: 2.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" ms")
+            .with_value_to_string(v2s_f32_scale(1000.0, 0))
+            .with_string_to_value(s2v_f32_scale(1000.0, " ms".to_owned())),
+
+            arpeggiator: ArpeggiatorParams::new(),
+
             note_state: Default::default(),
         }
     }
@@ -121,7 +681,7 @@ impl Plugin for MyPlugin {
     // IO ports
     const DEFAULT_INPUT_CHANNELS: u32 = 0;
     const DEFAULT_OUTPUT_CHANNELS: u32 = 2;
-    const MIDI_INPUT: MidiConfig = MidiConfig::None;
+    const MIDI_INPUT: MidiConfig = MidiConfig::MidiCCs;
     const MIDI_OUTPUT: MidiConfig = MidiConfig::None;
 
     type BackgroundTask = ();
@@ -130,44 +690,41 @@ impl Plugin for MyPlugin {
         self.params.clone()
     }
 
+    // stereo by default, but mono hosts get downmix instead of left channel only
+    fn accepts_bus_config(&self, config: &BusConfig) -> bool {
+        config.num_input_channels == 0 && (1..=2).contains(&config.num_output_channels)
+    }
+
+    fn initialize(
+        &mut self,
+        _bus_config: &BusConfig,
+        buffer_config: &BufferConfig,
+        _context: &mut impl InitContext<Self>,
+    ) -> bool {
+        self.meter.initialize(buffer_config.sample_rate);
+        self.effects.initialize(buffer_config.sample_rate);
+        self.analyzer_ring
+            .set_sample_rate(buffer_config.sample_rate);
+        true
+    }
+
+    fn reset(&mut self) {
+        self.effects.reset();
+    }
+
     fn editor(&self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         let params = self.params.clone();
+        let meter_levels = self.meter_levels.clone();
+        let analyzer_ring = self.analyzer_ring.clone();
+        let wavetable_bank = self.wavetable_bank.clone();
+        let wavetable_promise: Arc<Mutex<Option<poll_promise::Promise<Result<(), String>>>>> =
+            Default::default();
         create_egui_editor(
             params.editor_state.clone(),
-            (),
+            (AnalyzerView::new(), TuningView::new()),
             |_, _| {},
-            move |egui_ctx, setter, _state| {
+            move |egui_ctx, setter, (analyzer_view, tuning_view)| {
                 egui::CentralPanel::default().show(egui_ctx, |ui| {
-                    egui::Grid::new("params")
-                        .num_columns(2)
-                        .spacing([20.0, 4.0])
-                        .show(ui, |ui| {
-                            ui.label("Gain");
-                            ui.add(widgets::ParamSlider::for_param(&params.gain, setter));
-                            ui.end_row();
-
-                            ui.label("Oscillator");
-                            combo_box_for_enum_param(
-                                egui::ComboBox::from_id_source("oscillator"),
-                                ui,
-                                &params.oscillator_type,
-                                setter,
-                            );
-                            ui.end_row();
-
-                            ui.label("Attack");
-                            ui.add(widgets::ParamSlider::for_param(&params.attack, setter));
-                            ui.end_row();
-
-                            ui.label("Release");
-                            ui.add(widgets::ParamSlider::for_param(&params.release, setter));
-                            ui.end_row();
-
-                            ui.label("Note");
-                            ui.add(widgets::ParamSlider::for_param(&params.note, setter));
-                            ui.end_row();
-                        });
-
                     let is_on = params.note_state.get() == NOTE_STATE_ON;
                     let button_clicked = ui.button(if is_on { "Pause" } else { "Play" }).clicked();
                     let key_pressed = ui
@@ -176,6 +733,501 @@ impl Plugin for MyPlugin {
                     if button_clicked || key_pressed {
                         params.note_state.enqueue(!is_on);
                     }
+
+                    analyzer_view.ui(ui, &analyzer_ring, egui::vec2(ui.available_width(), 120.0));
+
+                    ui.separator();
+
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("params")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("Gain");
+                                ui.horizontal(|ui| {
+                                    ui.add(widgets::ParamSlider::for_param(&params.gain, setter));
+                                    meter::meter_ui(ui, &meter_levels);
+                                });
+                                ui.end_row();
+
+                                ui.label("Pan");
+                                ui.add(widgets::ParamSlider::for_param(&params.pan, setter));
+                                ui.end_row();
+
+                                ui.label("Pan Spread");
+                                ui.add(widgets::ParamSlider::for_param(&params.pan_spread, setter));
+                                ui.end_row();
+
+                                ui.label("Width");
+                                ui.add(widgets::ParamSlider::for_param(&params.width, setter));
+                                ui.end_row();
+
+                                ui.label("Synthesis");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("synthesis"),
+                                    ui,
+                                    &params.synthesis,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Oscillator");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("oscillator"),
+                                    ui,
+                                    &params.oscillator_type,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                //
+                                // wavetable selector and asynchronous loading (same as soundfont_player)
+                                //
+                                ui.label("Wavetable");
+                                ui.horizontal(|ui| {
+                                    let selected = wavetable_bank.selected();
+                                    egui::ComboBox::from_id_source("wavetable")
+                                        .width(150.0)
+                                        .selected_text(&selected.name)
+                                        .show_ui(ui, |ui| {
+                                            for table in wavetable_bank.tables() {
+                                                let is_selected = Arc::ptr_eq(&table, &selected);
+                                                let label = format!(
+                                                    "{} ({} frames)",
+                                                    table.name,
+                                                    table.num_frames()
+                                                );
+                                                if ui.selectable_label(is_selected, label).clicked()
+                                                    && !is_selected
+                                                {
+                                                    wavetable_bank.select(&table);
+                                                }
+                                            }
+                                        });
+
+                                    let mut wavetable_promise = wavetable_promise.lock().unwrap();
+                                    let mut is_loading = false;
+                                    let mut error = None;
+                                    if let Some(promise) = wavetable_promise.as_ref() {
+                                        match promise.ready() {
+                                            None => {
+                                                is_loading = true;
+                                            }
+                                            Some(Ok(())) => {
+                                                // reset promise on success
+                                                *wavetable_promise = None;
+                                            }
+                                            Some(Err(e)) => {
+                                                error = Some(e.clone());
+                                            }
+                                        }
+                                    }
+
+                                    if ui
+                                        .button(if is_loading {
+                                            "Loading…"
+                                        } else {
+                                            "Load File"
+                                        })
+                                        .clicked()
+                                        && !is_loading
+                                    {
+                                        if let Some(path) = rfd::FileDialog::new()
+                                            .add_filter("WAV", &["wav"])
+                                            .pick_file()
+                                        {
+                                            let wavetable_bank = wavetable_bank.clone();
+                                            *wavetable_promise =
+                                                Some(poll_promise::Promise::spawn_thread(
+                                                    "load-wavetable-file",
+                                                    move || {
+                                                        let table = Wavetable::load(&path)?;
+                                                        wavetable_bank.add(table);
+                                                        Ok(())
+                                                    },
+                                                ));
+                                        }
+                                    }
+
+                                    if let Some(error) = error {
+                                        ui.label(
+                                            egui::RichText::new("ERROR").color(egui::Color32::RED),
+                                        )
+                                        .on_hover_text(error);
+                                    }
+                                });
+                                ui.end_row();
+
+                                ui.label("Position");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.wavetable_position,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Unison");
+                                ui.add(widgets::ParamSlider::for_param(&params.unison, setter));
+                                ui.end_row();
+
+                                ui.label("Detune");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.unison_detune,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Spread");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.unison_spread,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Sub");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("sub_type"),
+                                    ui,
+                                    &params.sub_type,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Sub Octave");
+                                ui.add(widgets::ParamSlider::for_param(&params.sub_octave, setter));
+                                ui.end_row();
+
+                                ui.label("Sub Level");
+                                ui.add(widgets::ParamSlider::for_param(&params.sub_level, setter));
+                                ui.end_row();
+
+                                ui.label("Noise");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("noise_type"),
+                                    ui,
+                                    &params.noise_type,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Noise Level");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.noise_level,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Attack");
+                                ui.add(widgets::ParamSlider::for_param(&params.attack, setter));
+                                ui.end_row();
+
+                                ui.label("Attack Curve");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.attack_curve,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Decay");
+                                ui.add(widgets::ParamSlider::for_param(&params.decay, setter));
+                                ui.end_row();
+
+                                ui.label("Decay Curve");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.decay_curve,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Sustain");
+                                ui.add(widgets::ParamSlider::for_param(&params.sustain, setter));
+                                ui.end_row();
+
+                                ui.label("Release");
+                                ui.add(widgets::ParamSlider::for_param(&params.release, setter));
+                                ui.end_row();
+
+                                ui.label("Release Curve");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.release_curve,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Note");
+                                ui.add(widgets::ParamSlider::for_param(&params.note, setter));
+                                ui.end_row();
+
+                                ui.label("Polyphony");
+                                ui.add(widgets::ParamSlider::for_param(&params.polyphony, setter));
+                                ui.end_row();
+
+                                ui.label("Retrigger");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("retrigger"),
+                                    ui,
+                                    &params.retrigger,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Glide");
+                                ui.horizontal(|ui| {
+                                    combo_box_for_enum_param(
+                                        egui::ComboBox::from_id_source("glide_mode"),
+                                        ui,
+                                        &params.glide_mode,
+                                        setter,
+                                    );
+                                    combo_box_for_enum_param(
+                                        egui::ComboBox::from_id_source("glide_type"),
+                                        ui,
+                                        &params.glide_type,
+                                        setter,
+                                    );
+                                });
+                                ui.end_row();
+
+                                ui.label("Glide Time");
+                                ui.add(widgets::ParamSlider::for_param(&params.glide_time, setter));
+                                ui.end_row();
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("filter")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("Filter");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("filter_type"),
+                                    ui,
+                                    &params.filter_type,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Cutoff");
+                                ui.add(widgets::ParamSlider::for_param(&params.cutoff, setter));
+                                ui.end_row();
+
+                                ui.label("Resonance");
+                                ui.add(widgets::ParamSlider::for_param(&params.resonance, setter));
+                                ui.end_row();
+
+                                ui.label("Env Amount");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.filter_envelope_amount,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Env Attack");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.filter_attack,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Env Decay");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.filter_decay,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Env Sustain");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.filter_sustain,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Env Release");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.filter_release,
+                                    setter,
+                                ));
+                                ui.end_row();
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("fm")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("FM Ratio");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_ratio, setter));
+                                ui.end_row();
+
+                                ui.label("FM Fine");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_fine, setter));
+                                ui.end_row();
+
+                                ui.label("FM Index");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_index, setter));
+                                ui.end_row();
+
+                                ui.label("Env Attack");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_attack, setter));
+                                ui.end_row();
+
+                                ui.label("Env Decay");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_decay, setter));
+                                ui.end_row();
+
+                                ui.label("Env Sustain");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_sustain, setter));
+                                ui.end_row();
+
+                                ui.label("Env Release");
+                                ui.add(widgets::ParamSlider::for_param(&params.fm_release, setter));
+                                ui.end_row();
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("expression")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("Bend Range");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.pitch_bend_range,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Vibrato Rate");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.vibrato_rate,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Vibrato Depth");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.vibrato_depth,
+                                    setter,
+                                ));
+                                ui.end_row();
+
+                                ui.label("Pressure");
+                                combo_box_for_enum_param(
+                                    egui::ComboBox::from_id_source("pressure_destination"),
+                                    ui,
+                                    &params.pressure_destination,
+                                    setter,
+                                );
+                                ui.end_row();
+
+                                ui.label("Pressure Amount");
+                                ui.add(widgets::ParamSlider::for_param(
+                                    &params.pressure_amount,
+                                    setter,
+                                ));
+                                ui.end_row();
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("tuning")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                tuning_view.ui(ui, &params.tuning, &params.reference_pitch, setter);
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("effects")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                effects_ui(ui, &params.effects, setter);
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("arpeggiator")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                arpeggiator_ui(ui, &params.arpeggiator, setter);
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("lfos")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                for (index, lfo) in params.lfos.iter().enumerate() {
+                                    ui.label(format!("LFO {}", index + 1));
+                                    combo_box_for_enum_param(
+                                        egui::ComboBox::from_id_source(("lfo_shape", index)),
+                                        ui,
+                                        &lfo.shape,
+                                        setter,
+                                    );
+                                    ui.end_row();
+
+                                    ui.label("Sync");
+                                    ui.horizontal(|ui| {
+                                        let mut sync = lfo.sync.value();
+                                        if ui.checkbox(&mut sync, "").changed() {
+                                            setter.begin_set_parameter(&lfo.sync);
+                                            setter.set_parameter(&lfo.sync, sync);
+                                            setter.end_set_parameter(&lfo.sync);
+                                        }
+                                        if sync {
+                                            combo_box_for_enum_param(
+                                                egui::ComboBox::from_id_source((
+                                                    "lfo_division",
+                                                    index,
+                                                )),
+                                                ui,
+                                                &lfo.division,
+                                                setter,
+                                            );
+                                        } else {
+                                            ui.add(widgets::ParamSlider::for_param(
+                                                &lfo.rate, setter,
+                                            ));
+                                        }
+                                    });
+                                    ui.end_row();
+                                }
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("mod_slots")
+                            .num_columns(4)
+                            .spacing([10.0, 4.0])
+                            .show(ui, |ui| {
+                                for (index, slot) in params.mod_slots.iter().enumerate() {
+                                    ui.label(format!("Mod {}", index + 1));
+                                    combo_box_for_enum_param(
+                                        egui::ComboBox::from_id_source(("mod_source", index))
+                                            .width(80.0),
+                                        ui,
+                                        &slot.source,
+                                        setter,
+                                    );
+                                    combo_box_for_enum_param(
+                                        egui::ComboBox::from_id_source(("mod_destination", index))
+                                            .width(80.0),
+                                        ui,
+                                        &slot.destination,
+                                        setter,
+                                    );
+                                    ui.add(widgets::ParamSlider::for_param(&slot.depth, setter));
+                                    ui.end_row();
+                                }
+                            });
+                    });
                 });
             },
         )
@@ -187,40 +1239,341 @@ impl Plugin for MyPlugin {
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
+        let transport = TransportInfo::new(context.transport());
+        self.process_block(buffer.as_slice(), &transport, || context.next_event());
+        ProcessStatus::Normal
+    }
+}
+
+impl MyPlugin {
+    // body of `Plugin::process` which doesn't depend on host (so that `render` can drive it)
+    fn process_block(
+        &mut self,
+        buffer: &mut [&mut [f32]],
+        transport: &TransportInfo,
+        mut next_event: impl FnMut() -> Option<NoteEvent>,
+    ) {
+        let polyphony = self.params.polyphony.value() as usize;
+        let retrigger = self.params.retrigger.value();
+        let glide = Glide {
+            mode: self.params.glide_mode.value(),
+            glide_type: self.params.glide_type.value(),
+            time: self.params.glide_time.value(),
+        };
+
+        // switching arpeggiator either way shouldn't leave hanging notes
+        if self.params.arpeggiator.enabled.value() != self.arpeggiator.enabled {
+            self.arpeggiator.reset();
+            self.voice_pool.release_all();
+        }
+        self.arpeggiator.update(&self.params.arpeggiator, transport);
+
+        // pick up tuning loaded on gui thread
+        Tuning::try_update(&self.params.tuning, &mut self.tuning_table);
+
         // handle note state sent from UI
         match self.params.note_state.dequeue() {
             Some(true) => {
-                self.oscillator.phase = 0.0;
-                self.envelop.stage = EnvelopeStage::Attack(0.0);
+                let note: u8 = self.params.note.value().try_into().unwrap();
+                self.ui_note = Some(note);
+                self.note_on(note, 1.0, polyphony, retrigger, glide);
             }
             Some(false) => {
-                self.envelop.stage = EnvelopeStage::Release(0.0);
+                if let Some(note) = self.ui_note.take() {
+                    self.note_off(note);
+                }
+            }
+            _ => {
+                // `note` param changed while playing is treated as legato note (so it can glide)
+                let note: u8 = self.params.note.value().try_into().unwrap();
+                if let Some(current) = self.ui_note.filter(|current| *current != note) {
+                    self.ui_note = Some(note);
+                    self.note_on(note, 1.0, polyphony, retrigger, glide);
+                    self.note_off(current);
+                }
             }
-            _ => {}
         }
 
+        // pick up wavetable selected on gui thread
+        self.wavetable_bank.try_update(&mut self.wavetable);
+
         // sync params
-        let note: u8 = self.params.note.value().try_into().unwrap();
-        self.oscillator.frequency = nih_plug::util::midi_note_to_freq(note);
-        self.oscillator.oscillator_type = self.params.oscillator_type.value();
-        self.envelop.attack_duration = self.params.attack.value();
-        self.envelop.release_duration = self.params.release.value();
+        let synthesis = self.params.synthesis.value();
+        let oscillator_type = self.params.oscillator_type.value();
+        let unison = self.params.unison.value() as usize;
+        let unison_detune = self.params.unison_detune.value();
+        let unison_spread = self.params.unison_spread.value();
+        let sub_type = self.params.sub_type.value();
+        let sub_octave = self.params.sub_octave.value();
+        let noise_type = self.params.noise_type.value();
+        let attack = self.params.attack.value();
+        let attack_curve = self.params.attack_curve.value();
+        let decay = self.params.decay.value();
+        let decay_curve = self.params.decay_curve.value();
+        let sustain = self.params.sustain.value();
+        let release = self.params.release.value();
+        let release_curve = self.params.release_curve.value();
+        let filter_type = self.params.filter_type.value();
+        let resonance = self.params.resonance.value();
+        let filter_envelope_amount = self.params.filter_envelope_amount.value();
+        let filter_attack = self.params.filter_attack.value();
+        let filter_decay = self.params.filter_decay.value();
+        let filter_sustain = self.params.filter_sustain.value();
+        let filter_release = self.params.filter_release.value();
+        let fm_ratio = self.params.fm_ratio.value() * (self.params.fm_fine.value() / 1200.0).exp2();
+        let fm_attack = self.params.fm_attack.value();
+        let fm_decay = self.params.fm_decay.value();
+        let fm_sustain = self.params.fm_sustain.value();
+        let fm_release = self.params.fm_release.value();
+        let pressure_destination = self.params.pressure_destination.value();
+        let pressure_amount = self.params.pressure_amount.value();
+        for voice in self.voice_pool.voices_mut() {
+            for oscillator in voice.oscillators.iter_mut() {
+                oscillator.oscillator_type = oscillator_type;
+            }
+            voice.unison = unison;
+            voice.unison_detune = unison_detune;
+            voice.unison_spread = unison_spread;
+            voice.sub_oscillator.oscillator_type = sub_type;
+            voice.sub_octave = sub_octave;
+            voice.noise.noise_type = noise_type;
+            voice.envelope.attack_duration = attack;
+            voice.envelope.attack_curve = attack_curve;
+            voice.envelope.decay_duration = decay;
+            voice.envelope.decay_curve = decay_curve;
+            voice.envelope.sustain_level = sustain;
+            voice.envelope.release_duration = release;
+            voice.envelope.release_curve = release_curve;
+            for filter in voice.filters.iter_mut() {
+                filter.filter_type = filter_type;
+                filter.resonance = resonance;
+            }
+            voice.filter_envelope_amount = filter_envelope_amount;
+            voice.filter_envelope.attack_duration = filter_attack;
+            voice.filter_envelope.decay_duration = filter_decay;
+            voice.filter_envelope.sustain_level = filter_sustain;
+            voice.filter_envelope.release_duration = filter_release;
+            voice.synthesis = synthesis;
+            voice.fm_ratio = fm_ratio;
+            voice.fm_envelope.attack_duration = fm_attack;
+            voice.fm_envelope.decay_duration = fm_decay;
+            voice.fm_envelope.sustain_level = fm_sustain;
+            voice.fm_envelope.release_duration = fm_release;
+            voice.pressure_destination = pressure_destination;
+            voice.pressure_amount = pressure_amount;
+        }
+        let pitch_bend_range = self.params.pitch_bend_range.value() as f32;
+        let vibrato_depth = self.params.vibrato_depth.value();
+        self.vibrato.frequency = self.params.vibrato_rate.value();
+
+        for (lfo, lfo_params) in self.lfos.iter_mut().zip(self.params.lfos.iter()) {
+            lfo.update(lfo_params, transport);
+        }
+        for (slot, slot_params) in self.mod_slots.iter_mut().zip(self.params.mod_slots.iter()) {
+            slot.source = slot_params.source.value();
+            slot.destination = slot_params.destination.value();
+        }
+        self.effects.update(&self.params.effects, transport);
 
         // synthesize
-        let sample_rate = context.transport().sample_rate;
+        let sample_rate = transport.sample_rate;
         let duration_delta = sample_rate.recip();
 
-        for samples in buffer.iter_samples() {
+        // split the loop at midi event timing so that notes start/stop at the exact sample
+        let num_samples = buffer.first().map_or(0, |channel| channel.len());
+        let mut event = next_event();
+        for sample_id in 0..num_samples {
+            while let Some(current) = event {
+                if current.timing() as usize > sample_id {
+                    break;
+                }
+                self.handle_event(current, polyphony, retrigger, glide, sample_rate);
+                event = next_event();
+            }
+
+            let arp_events = self.arpeggiator.next();
+            if let Some(note) = arp_events.note_off {
+                self.voice_pool.note_off(note);
+            }
+            if let Some((note, velocity)) = arp_events.note_on {
+                self.play_note(note, velocity, polyphony, retrigger, glide);
+            }
+
             let gain = self.params.gain.smoothed.next();
-            let envelope = self.envelop.next(duration_delta);
-            let sine = self.oscillator.next(duration_delta);
-            let value = gain * envelope * sine;
-            for sample in samples {
-                *sample = value;
+            let cutoff = self.params.cutoff.smoothed.next();
+            let wavetable_position = self.params.wavetable_position.smoothed.next();
+            let fm_index = self.params.fm_index.smoothed.next();
+            let sub_level = self.params.sub_level.smoothed.next();
+            let noise_level = self.params.noise_level.smoothed.next();
+            let pan = self.params.pan.smoothed.next();
+            let pan_spread = self.params.pan_spread.smoothed.next();
+            let width = self.params.width.smoothed.next();
+            let vibrato = self.mod_wheel.next()
+                * vibrato_depth
+                * self.vibrato.next(duration_delta, &self.wavetable);
+            let pitch_offset = self.pitch_bend.next() * pitch_bend_range + vibrato;
+            let channel_pressure = self.channel_pressure.next();
+            for (slot, slot_params) in self.mod_slots.iter_mut().zip(self.params.mod_slots.iter()) {
+                slot.depth = slot_params.depth.smoothed.next();
+            }
+            let mut lfos = [0.0; NUM_LFOS];
+            for (value, lfo) in lfos.iter_mut().zip(self.lfos.iter_mut()) {
+                *value = lfo.next(duration_delta, &self.wavetable);
+            }
+
+            let mut left = 0.0;
+            let mut right = 0.0;
+            for voice in self.voice_pool.active_voices_mut() {
+                voice.fm_index = fm_index;
+                voice.sub_level = sub_level;
+                voice.noise_level = noise_level;
+                voice.pan = pan;
+                voice.pan_spread = pan_spread;
+                voice.pitch_offset = pitch_offset;
+                voice.channel_pressure = channel_pressure;
+                let (voice_left, voice_right) = voice.next(
+                    duration_delta,
+                    cutoff,
+                    &self.wavetable,
+                    wavetable_position,
+                    &lfos,
+                    &self.mod_slots,
+                );
+                left += voice_left;
+                right += voice_right;
+            }
+
+            let (left, right) =
+                self.effects
+                    .next(&self.params.effects, (left, right), duration_delta);
+
+            let (left, right) = stereo_width(left, right, width);
+
+            // downmix to mono layout (unity gain for centered sound)
+            if let [output] = buffer {
+                output[sample_id] = gain * 0.5 * (left + right);
+                continue;
+            }
+            for (channel, output) in buffer.iter_mut().enumerate() {
+                output[sample_id] = gain * if channel == 0 { left } else { right };
             }
         }
 
-        ProcessStatus::Normal
+        // events beyond the buffer shouldn't happen, but don't lose note-off in that case
+        while let Some(current) = event {
+            self.handle_event(current, polyphony, retrigger, glide, sample_rate);
+            event = next_event();
+        }
+
+        // publish output levels and samples only while someone is looking at them
+        if self.params.editor_state.is_open() {
+            self.meter.process(buffer);
+            self.analyzer_ring.push_buffer(buffer);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        event: NoteEvent,
+        polyphony: usize,
+        retrigger: RetriggerMode,
+        glide: Glide,
+        sample_rate: f32,
+    ) {
+        match event {
+            NoteEvent::NoteOn { note, velocity, .. } => {
+                self.note_on(note, velocity, polyphony, retrigger, glide);
+            }
+            NoteEvent::NoteOff { note, .. } => {
+                self.note_off(note);
+            }
+            // centered at 0.5
+            NoteEvent::MidiPitchBend { value, .. } => {
+                self.pitch_bend.set_target(sample_rate, 2.0 * value - 1.0);
+            }
+            NoteEvent::MidiCC { cc, value, .. } if cc == control_change::MODULATION_MSB => {
+                self.mod_wheel.set_target(sample_rate, value);
+            }
+            NoteEvent::MidiChannelPressure { pressure, .. } => {
+                self.channel_pressure.set_target(sample_rate, pressure);
+            }
+            NoteEvent::PolyPressure { note, pressure, .. } => {
+                self.voice_pool.poly_pressure(note, pressure, sample_rate);
+            }
+            _ => {}
+        }
+    }
+
+    // incoming notes go through arpeggiator while it's enabled
+    fn note_on(
+        &mut self,
+        note: u8,
+        velocity: f32,
+        polyphony: usize,
+        retrigger: RetriggerMode,
+        glide: Glide,
+    ) {
+        if self.arpeggiator.enabled {
+            self.arpeggiator.note_on(note, velocity);
+        } else {
+            self.play_note(note, velocity, polyphony, retrigger, glide);
+        }
+    }
+
+    fn note_off(&mut self, note: u8) {
+        if self.arpeggiator.enabled {
+            self.arpeggiator.note_off(note);
+        } else {
+            self.voice_pool.note_off(note);
+        }
+    }
+
+    // unmapped keys of the keyboard mapping are silent
+    fn play_note(
+        &mut self,
+        note: u8,
+        velocity: f32,
+        polyphony: usize,
+        retrigger: RetriggerMode,
+        glide: Glide,
+    ) {
+        if let Some(frequency) = self.frequency(note) {
+            self.voice_pool
+                .note_on(note, frequency, velocity, polyphony, retrigger, glide);
+        }
+    }
+
+    fn frequency(&self, note: u8) -> Option<f32> {
+        self.tuning_table
+            .frequency(note, self.params.reference_pitch.value())
+    }
+}
+
+//
+// TransportInfo
+//
+
+// subset of host `Transport` used by `process_block`
+// (nih-plug's `Transport` can't be constructed outside of its wrappers)
+#[derive(Debug, Clone, Copy)]
+struct TransportInfo {
+    sample_rate: f32,
+    tempo: Option<f64>,
+    // only while playing
+    pos_beats: Option<f64>,
+}
+
+impl TransportInfo {
+    fn new(transport: &Transport) -> Self {
+        Self {
+            sample_rate: transport.sample_rate,
+            tempo: transport.tempo,
+            pos_beats: if transport.playing {
+                transport.pos_beats()
+            } else {
+                None
+            },
+        }
     }
 }
 
@@ -252,16 +1605,38 @@ fn combo_box_for_enum_param<
     }
 }
 
+//
+// param_checkbox
+//
+
+fn param_checkbox(ui: &mut egui::Ui, param: &BoolParam, text: &str, setter: &ParamSetter) {
+    let mut value = param.value();
+    if ui.checkbox(&mut value, text).changed() {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, value);
+        setter.end_set_parameter(param);
+    }
+}
+
 //
 // Oscillator
 //
 
+// `Square`, `Triangle` and `Sawtooth` are band-limited by PolyBLEP/PolyBLAMP
+// and the naive shapes are kept as "raw" variants (appended to keep the existing parameter values)
 #[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
 enum OscillatorType {
     Sine,
     Square,
     Triangle,
     Sawtooth,
+    #[name = "Square (raw)"]
+    SquareRaw,
+    #[name = "Triangle (raw)"]
+    TriangleRaw,
+    #[name = "Sawtooth (raw)"]
+    SawtoothRaw,
+    Wavetable,
 }
 
 // normalize peak based on square integral norm
@@ -271,9 +1646,11 @@ impl OscillatorType {
     fn norm(self) -> f32 {
         let square: f32 = match self {
             OscillatorType::Sine => 0.5,
-            OscillatorType::Square => 1.0,
-            OscillatorType::Triangle => 1.0 / 3.0,
-            OscillatorType::Sawtooth => 1.0 / 3.0,
+            OscillatorType::Square | OscillatorType::SquareRaw => 1.0,
+            OscillatorType::Triangle | OscillatorType::TriangleRaw => 1.0 / 3.0,
+            OscillatorType::Sawtooth | OscillatorType::SawtoothRaw => 1.0 / 3.0,
+            // normalized on load
+            OscillatorType::Wavetable => 0.5,
         };
         square.sqrt()
     }
@@ -288,6 +1665,10 @@ struct Oscillator {
     oscillator_type: OscillatorType,
     phase: f32,
     frequency: f32,
+    // frame position for `OscillatorType::Wavetable`
+    wavetable_position: f32,
+    // offset added to `phase` (phase modulation in cycles)
+    phase_modulation: f32,
 }
 
 impl Oscillator {
@@ -296,34 +1677,75 @@ impl Oscillator {
             oscillator_type: OscillatorType::Sine,
             phase: 0.0,
             frequency: 440.0,
+            wavetable_position: 0.0,
+            phase_modulation: 0.0,
         }
     }
 
-    fn next(&mut self, delta: f32) -> f32 {
-        let mut t = self.phase;
+    fn next(&mut self, delta: f32, wavetable: &Wavetable) -> f32 {
+        let t = (self.phase + self.phase_modulation).rem_euclid(1.0);
+        let dt = self.frequency * delta;
         let value = match self.oscillator_type {
             OscillatorType::Sine => (TAU * t).sin(),
-            OscillatorType::Square => (t - 0.5).signum(),
-            OscillatorType::Triangle => (-4.0 * t + 2.0).abs() - 1.0,
-            OscillatorType::Sawtooth => 2.0 * t - 1.0,
+            OscillatorType::Square => {
+                let t_half = (t + 0.5) % 1.0;
+                (t - 0.5).signum() - poly_blep(t, dt) + poly_blep(t_half, dt)
+            }
+            OscillatorType::Triangle => {
+                // slope jumps by -8 at the peak (t = 0) and by +8 at the bottom (t = 0.5)
+                let t_half = (t + 0.5) % 1.0;
+                (-4.0 * t + 2.0).abs() - 1.0
+                    + 8.0 * dt * (poly_blamp(t_half, dt) - poly_blamp(t, dt))
+            }
+            OscillatorType::Sawtooth => 2.0 * t - 1.0 - poly_blep(t, dt),
+            OscillatorType::SquareRaw => (t - 0.5).signum(),
+            OscillatorType::TriangleRaw => (-4.0 * t + 2.0).abs() - 1.0,
+            OscillatorType::SawtoothRaw => 2.0 * t - 1.0,
+            OscillatorType::Wavetable => wavetable.value(self.wavetable_position, t),
         } * self.oscillator_type.factor();
-        t += self.frequency * delta;
-        t %= 1.0;
-        self.phase = t;
+        self.phase = (self.phase + dt) % 1.0;
         value
     }
 }
 
+// residual of band-limited step (polynomial approximation of BLEP) for a jump of +2 at phase 0
+// where `dt` is phase increment per sample
+// (cf. Välimäki and Huovilainen, "Antialiasing Oscillators in Subtractive Synthesis")
+fn poly_blep(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = t / dt;
+        2.0 * x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + 2.0 * x + 1.0
+    } else {
+        0.0
+    }
+}
+
+// residual of band-limited ramp (integral of `poly_blep` for a unit jump) in unit of samples,
+// which corrects slope discontinuity when multiplied by "slope jump" * `dt`
+fn poly_blamp(t: f32, dt: f32) -> f32 {
+    if t < dt {
+        let x = 1.0 - t / dt;
+        x * x * x / 6.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt + 1.0;
+        x * x * x / 6.0
+    } else {
+        0.0
+    }
+}
+
 //
 // Envelope
 //
 
-// TODO: decay, sustain
-
 #[derive(Debug, Copy, Clone)]
 enum EnvelopeStage {
     Off,
     Attack(f32),
+    Decay(f32),
     Sustain,
     Release(f32),
 }
@@ -331,35 +1753,86 @@ enum EnvelopeStage {
 #[derive(Debug)]
 struct Envelope {
     stage: EnvelopeStage,
+    // current output level and the level where the current attack/release stage started from,
+    // so that re-attack and release continue from where the envelope is instead of jumping
+    level: f32,
+    start_level: f32,
     attack_duration: f32,
+    attack_curve: f32,
+    decay_duration: f32,
+    decay_curve: f32,
+    sustain_level: f32,
     release_duration: f32,
+    release_curve: f32,
 }
 
 impl Envelope {
     fn new() -> Self {
         Self {
             stage: EnvelopeStage::Off,
+            level: 0.0,
+            start_level: 0.0,
             attack_duration: 0.01,
+            attack_curve: 0.0,
+            decay_duration: 0.1,
+            decay_curve: 0.0,
+            sustain_level: 1.0,
             release_duration: 0.1,
+            release_curve: 0.0,
         }
     }
 
+    // restart attack from the current level
+    fn trigger(&mut self) {
+        self.start_level = self.level;
+        self.stage = EnvelopeStage::Attack(0.0);
+    }
+
+    // restart attack from zero
+    fn reset(&mut self) {
+        self.level = 0.0;
+        self.trigger();
+    }
+
+    fn release(&mut self) {
+        self.start_level = self.level;
+        self.stage = EnvelopeStage::Release(0.0);
+    }
+
     fn next(&mut self, delta: f32) -> f32 {
+        self.level = self.next_level(delta);
+        self.level
+    }
+
+    fn next_level(&mut self, delta: f32) -> f32 {
         match self.stage {
             EnvelopeStage::Off => 0.0,
             EnvelopeStage::Attack(mut t) => {
-                let value = t / self.attack_duration;
+                let progress = apply_curve(t / self.attack_duration, self.attack_curve);
+                let value = self.start_level + (1.0 - self.start_level) * progress;
                 t += delta;
                 if t < self.attack_duration {
                     self.stage = EnvelopeStage::Attack(t);
+                } else {
+                    self.stage = EnvelopeStage::Decay(0.0);
+                }
+                value
+            }
+            EnvelopeStage::Decay(mut t) => {
+                let progress = apply_curve(t / self.decay_duration, self.decay_curve);
+                let value = 1.0 - (1.0 - self.sustain_level) * progress;
+                t += delta;
+                if t < self.decay_duration {
+                    self.stage = EnvelopeStage::Decay(t);
                 } else {
                     self.stage = EnvelopeStage::Sustain;
                 }
                 value
             }
-            EnvelopeStage::Sustain => 1.0,
+            EnvelopeStage::Sustain => self.sustain_level,
             EnvelopeStage::Release(mut t) => {
-                let value = 1.0 - t / self.release_duration;
+                let progress = apply_curve(t / self.release_duration, self.release_curve);
+                let value = self.start_level * (1.0 - progress);
                 t += delta;
                 if t < self.release_duration {
                     self.stage = EnvelopeStage::Release(t);
@@ -372,6 +1845,64 @@ impl Envelope {
     }
 }
 
+// map linear progress `x` in [0, 1] to curved progress in [0, 1]
+//   curve > 0 : exponential (slow start, fast end)
+//   curve = 0 : linear
+//   curve < 0 : logarithmic (fast start, slow end)
+fn apply_curve(x: f32, curve: f32) -> f32 {
+    const STEEPNESS: f32 = 6.0;
+    let k = curve * STEEPNESS;
+    if k.abs() < 1e-3 {
+        return x;
+    }
+    (k * x).exp_m1() / k.exp_m1()
+}
+
+//
+// pan law
+//
+
+// constant power panning compensated to unity gain at center (`pan` in [-1, 1])
+fn pan_gains(pan: f32) -> (f32, f32) {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    (SQRT_2 * angle.cos(), SQRT_2 * angle.sin())
+}
+
+// scale side signal by `width` (0 collapses to mono, 1 keeps as is)
+fn stereo_width(left: f32, right: f32, width: f32) -> (f32, f32) {
+    let mid = 0.5 * (left + right);
+    let side = 0.5 * width * (left - right);
+    (mid + side, mid - side)
+}
+
+//
+// random
+//
+
+// xorshift32 (deterministic and allocation-free for audio thread)
+#[derive(Debug)]
+struct Random(u32);
+
+impl Random {
+    fn new(seed: u32) -> Self {
+        Self(seed.max(1))
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+
+    // uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
+
 //
 // NoteState (copied from examples/midi_keyboard/src/lib.rs)
 //
@@ -434,3 +1965,113 @@ fn s2v_f32_scale(scale: f32, trim_end: String) -> Arc<dyn Fn(&str) -> Option<f32
         string.parse::<f32>().ok().map(|value| value / scale)
     })
 }
+
+//
+// envelope curve formatter
+//
+
+fn curve_param(name: &'static str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Linear {
+            min: -1.0,
+            max: 1.0,
+        },
+    )
+    .with_value_to_string(v2s_f32_curve())
+    .with_string_to_value(s2v_f32_curve())
+}
+
+fn v2s_f32_curve() -> Arc<dyn Fn(f32) -> String + Send + Sync> {
+    Arc::new(move |value| {
+        let percentage = (value.abs() * 100.0).round();
+        if percentage == 0.0 {
+            "Linear".to_owned()
+        } else if value > 0.0 {
+            format!("Exp {:.0}%", percentage)
+        } else {
+            format!("Log {:.0}%", percentage)
+        }
+    })
+}
+
+fn s2v_f32_curve() -> Arc<dyn Fn(&str) -> Option<f32> + Send + Sync> {
+    Arc::new(move |string| {
+        let string = string.trim().to_lowercase();
+        if string == "linear" {
+            return Some(0.0);
+        }
+        let (sign, rest) = if let Some(rest) = string.strip_prefix("exp") {
+            (1.0, rest)
+        } else if let Some(rest) = string.strip_prefix("log") {
+            (-1.0, rest)
+        } else {
+            return string.parse::<f32>().ok();
+        };
+        let rest = rest.trim().trim_end_matches('%');
+        rest.parse::<f32>().ok().map(|value| sign * value / 100.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // energy of `oscillator_type` outside of its harmonics (i.e. folded back by aliasing)
+    // relative to the energy of its harmonics. the frequency sits exactly on a DFT bin so that
+    // the harmonics don't leak and the aliases don't land on harmonics.
+    fn aliasing_ratio(oscillator_type: OscillatorType) -> f64 {
+        const SIZE: usize = 4800;
+        const SAMPLE_RATE: f32 = 48000.0;
+        const BIN: usize = 235;
+
+        let wavetable = Wavetable::sine();
+        let mut oscillator = Oscillator::new();
+        oscillator.oscillator_type = oscillator_type;
+        oscillator.frequency = BIN as f32 * SAMPLE_RATE / SIZE as f32;
+        // skip one block to start from a steady state
+        let samples: Vec<f64> = (0..2 * SIZE)
+            .map(|_| oscillator.next(1.0 / SAMPLE_RATE, &wavetable) as f64)
+            .skip(SIZE)
+            .collect();
+
+        // Parseval (energy of positive and negative frequencies)
+        let total: f64 = samples.iter().map(|x| x * x).sum();
+        let dft_energy = |bin: usize| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (n, x) in samples.iter().enumerate() {
+                let angle = std::f64::consts::TAU * (bin * n % SIZE) as f64 / SIZE as f64;
+                re += x * angle.cos();
+                im -= x * angle.sin();
+            }
+            (re * re + im * im) / SIZE as f64
+        };
+        let dc = dft_energy(0);
+        let harmonics: f64 = (1..)
+            .map(|harmonic| harmonic * BIN)
+            .take_while(|&bin| bin < SIZE / 2)
+            .map(|bin| 2.0 * dft_energy(bin))
+            .sum();
+        (total - dc - harmonics) / harmonics
+    }
+
+    #[test]
+    fn poly_blep_reduces_aliasing() {
+        for (band_limited, raw) in [
+            (OscillatorType::Sawtooth, OscillatorType::SawtoothRaw),
+            (OscillatorType::Square, OscillatorType::SquareRaw),
+        ] {
+            let band_limited_ratio = aliasing_ratio(band_limited);
+            let raw_ratio = aliasing_ratio(raw);
+            // at least 10 dB less
+            assert!(
+                band_limited_ratio < 0.1 * raw_ratio,
+                "{:?}: {} vs {}",
+                band_limited,
+                band_limited_ratio,
+                raw_ratio
+            );
+        }
+    }
+}
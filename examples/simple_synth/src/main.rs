@@ -2,5 +2,15 @@ use nih_plug::prelude::nih_export_standalone;
 use simple_synth::MyPlugin;
 
 fn main() {
+    // headless mode writing wav file (cf. `render.rs`)
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        if let Err(e) = simple_synth::render::run(&args[2..]) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     nih_export_standalone::<MyPlugin>();
 }
@@ -0,0 +1,238 @@
+use atomic_float::AtomicF32;
+use nih_plug::prelude::*;
+use nih_plug_egui::egui;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc,
+};
+
+pub(crate) const NUM_CHANNELS: usize = 2;
+
+// peak falls to 25% (-12 dB) in this duration
+const PEAK_DECAY_MS: f32 = 150.0;
+const PEAK_HOLD_MS: f32 = 1000.0;
+const RMS_WINDOW_MS: f32 = 300.0;
+
+// meter scale in dBFS
+const METER_MIN_DB: f32 = -60.0;
+const METER_MAX_DB: f32 = 6.0;
+
+//
+// levels published from audio thread to gui thread (in gain, not dB)
+//
+
+#[derive(Debug)]
+pub(crate) struct ChannelLevels {
+    peak: AtomicF32,
+    peak_hold: AtomicF32,
+    rms: AtomicF32,
+    // latched until reset from gui
+    clip: AtomicBool,
+}
+
+impl ChannelLevels {
+    fn new() -> Self {
+        Self {
+            peak: AtomicF32::new(0.0),
+            peak_hold: AtomicF32::new(0.0),
+            rms: AtomicF32::new(0.0),
+            clip: AtomicBool::new(false),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct MeterLevels {
+    channels: [ChannelLevels; NUM_CHANNELS],
+    // channels of the current output layout (1 for mono)
+    num_channels: AtomicUsize,
+}
+
+impl MeterLevels {
+    pub(crate) fn new() -> Self {
+        Self {
+            channels: [ChannelLevels::new(), ChannelLevels::new()],
+            num_channels: AtomicUsize::new(NUM_CHANNELS),
+        }
+    }
+}
+
+//
+// Meter (audio thread)
+//
+
+#[derive(Debug, Default, Clone, Copy)]
+struct ChannelMeter {
+    peak: f32,
+    peak_hold: f32,
+    hold_counter: usize,
+    mean_square: f32,
+}
+
+#[derive(Debug)]
+pub(crate) struct Meter {
+    levels: Arc<MeterLevels>,
+    channels: [ChannelMeter; NUM_CHANNELS],
+    peak_decay_weight: f32,
+    rms_weight: f32,
+    hold_samples: usize,
+}
+
+impl Meter {
+    pub(crate) fn new(levels: Arc<MeterLevels>) -> Self {
+        let mut meter = Self {
+            levels,
+            channels: Default::default(),
+            peak_decay_weight: 0.0,
+            rms_weight: 0.0,
+            hold_samples: 0,
+        };
+        meter.initialize(44100.0);
+        meter
+    }
+
+    pub(crate) fn initialize(&mut self, sample_rate: f32) {
+        self.channels = Default::default();
+        self.peak_decay_weight = 0.25f32.powf((sample_rate * PEAK_DECAY_MS / 1000.0).recip());
+        self.rms_weight = (-(sample_rate * RMS_WINDOW_MS / 1000.0).recip()).exp();
+        self.hold_samples = (sample_rate * PEAK_HOLD_MS / 1000.0) as usize;
+    }
+
+    pub(crate) fn process(&mut self, buffer: &[&mut [f32]]) {
+        self.levels
+            .num_channels
+            .store(buffer.len().min(NUM_CHANNELS), Ordering::Relaxed);
+        for ((meter, levels), samples) in self
+            .channels
+            .iter_mut()
+            .zip(self.levels.channels.iter())
+            .zip(buffer.iter())
+        {
+            let mut clip = false;
+            for &sample in samples.iter() {
+                let amplitude = sample.abs();
+                clip |= amplitude > 1.0;
+
+                meter.peak = amplitude.max(meter.peak * self.peak_decay_weight);
+
+                if meter.peak >= meter.peak_hold {
+                    meter.peak_hold = meter.peak;
+                    meter.hold_counter = self.hold_samples;
+                } else if meter.hold_counter > 0 {
+                    meter.hold_counter -= 1;
+                } else {
+                    meter.peak_hold *= self.peak_decay_weight;
+                }
+
+                meter.mean_square = self.rms_weight * meter.mean_square
+                    + (1.0 - self.rms_weight) * amplitude * amplitude;
+            }
+
+            levels.peak.store(meter.peak, Ordering::Relaxed);
+            levels.peak_hold.store(meter.peak_hold, Ordering::Relaxed);
+            levels
+                .rms
+                .store(meter.mean_square.sqrt(), Ordering::Relaxed);
+            if clip {
+                levels.clip.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+//
+// meter_ui (gui thread)
+//
+
+// horizontal bar per channel (rms filled, peak overlaid, peak hold as tick) and clip indicator (click to reset)
+pub(crate) fn meter_ui(ui: &mut egui::Ui, levels: &MeterLevels) {
+    const BAR_SIZE: egui::Vec2 = egui::Vec2::new(100.0, 8.0);
+    const GAP: f32 = 2.0;
+    const CLIP_WIDTH: f32 = 10.0;
+
+    let num_channels = levels.num_channels.load(Ordering::Relaxed);
+    let size = egui::vec2(
+        BAR_SIZE.x + GAP + CLIP_WIDTH,
+        num_channels as f32 * (BAR_SIZE.y + GAP) - GAP,
+    );
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+    let painter = ui.painter();
+
+    let to_x = |gain: f32| {
+        let db = util::gain_to_db(gain);
+        let t = ((db - METER_MIN_DB) / (METER_MAX_DB - METER_MIN_DB)).clamp(0.0, 1.0);
+        rect.left() + t * BAR_SIZE.x
+    };
+
+    let mut clip = false;
+    let mut tooltip = String::new();
+    for (index, channel) in levels.channels[..num_channels].iter().enumerate() {
+        let peak = channel.peak.load(Ordering::Relaxed);
+        let peak_hold = channel.peak_hold.load(Ordering::Relaxed);
+        let rms = channel.rms.load(Ordering::Relaxed);
+        clip |= channel.clip.load(Ordering::Relaxed);
+
+        let top = rect.top() + index as f32 * (BAR_SIZE.y + GAP);
+        let y_range = top..=(top + BAR_SIZE.y);
+        let bar = |x: f32| egui::Rect::from_x_y_ranges(rect.left()..=x, y_range.clone());
+
+        painter.rect_filled(
+            bar(rect.left() + BAR_SIZE.x),
+            0.0,
+            egui::Color32::from_gray(40),
+        );
+        painter.rect_filled(bar(to_x(peak)), 0.0, egui::Color32::from_rgb(40, 120, 40));
+        painter.rect_filled(bar(to_x(rms)), 0.0, egui::Color32::from_rgb(80, 200, 80));
+        let hold_x = to_x(peak_hold);
+        painter.line_segment(
+            [
+                egui::pos2(hold_x, top),
+                egui::pos2(hold_x, top + BAR_SIZE.y),
+            ],
+            egui::Stroke::new(1.0, egui::Color32::YELLOW),
+        );
+
+        tooltip += &format!(
+            "{}  peak {:.1} dB  rms {:.1} dB\n",
+            match (num_channels, index) {
+                (1, _) => "M",
+                (_, 0) => "L",
+                _ => "R",
+            },
+            util::gain_to_db(peak),
+            util::gain_to_db(rms),
+        );
+    }
+
+    // 0 dBFS marker
+    let zero_x = to_x(1.0);
+    painter.line_segment(
+        [
+            egui::pos2(zero_x, rect.top()),
+            egui::pos2(zero_x, rect.bottom()),
+        ],
+        egui::Stroke::new(1.0, egui::Color32::GRAY),
+    );
+
+    let clip_rect = egui::Rect::from_x_y_ranges(
+        (rect.right() - CLIP_WIDTH)..=rect.right(),
+        rect.top()..=rect.bottom(),
+    );
+    painter.rect_filled(
+        clip_rect,
+        1.0,
+        if clip {
+            egui::Color32::RED
+        } else {
+            egui::Color32::from_gray(40)
+        },
+    );
+
+    if response.clicked() {
+        for channel in levels.channels.iter() {
+            channel.clip.store(false, Ordering::Relaxed);
+        }
+    }
+
+    response.on_hover_text(tooltip.trim_end());
+}
@@ -0,0 +1,261 @@
+use crate::{wavetable::Wavetable, Oscillator, OscillatorType, TransportInfo};
+use nih_plug::prelude::*;
+
+pub(crate) const NUM_LFOS: usize = 2;
+pub(crate) const NUM_MOD_SLOTS: usize = 4;
+
+//
+// SyncDivision
+//
+
+// note length in tempo-synced mode
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum SyncDivision {
+    #[name = "4/1"]
+    FourBars,
+    #[name = "2/1"]
+    TwoBars,
+    #[name = "1/1"]
+    Whole,
+    #[name = "1/2"]
+    Half,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/32"]
+    ThirtySecond,
+}
+
+impl SyncDivision {
+    // length in quarter notes (i.e. beats)
+    pub(crate) fn beats(self) -> f64 {
+        match self {
+            SyncDivision::FourBars => 16.0,
+            SyncDivision::TwoBars => 8.0,
+            SyncDivision::Whole => 4.0,
+            SyncDivision::Half => 2.0,
+            SyncDivision::Quarter => 1.0,
+            SyncDivision::Eighth => 0.5,
+            SyncDivision::Sixteenth => 0.25,
+            SyncDivision::ThirtySecond => 0.125,
+        }
+    }
+}
+
+//
+// LfoParams
+//
+
+#[derive(Params)]
+pub(crate) struct LfoParams {
+    #[id = "lfo_shape"]
+    pub(crate) shape: EnumParam<OscillatorType>,
+
+    #[id = "lfo_rate"]
+    pub(crate) rate: FloatParam,
+
+    #[id = "lfo_sync"]
+    pub(crate) sync: BoolParam,
+
+    #[id = "lfo_division"]
+    pub(crate) division: EnumParam<SyncDivision>,
+}
+
+impl LfoParams {
+    pub(crate) fn new(index: usize) -> Self {
+        Self {
+            shape: EnumParam::new(format!("LFO {} Shape", index + 1), OscillatorType::Sine),
+
+            rate: FloatParam::new(
+                format!("LFO {} Rate", index + 1),
+                1.0,
+                FloatRange::Skewed {
+                    min: 0.01,
+                    max: 20.0,
+                    factor: FloatRange::skew_factor(-2.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+
+            sync: BoolParam::new(format!("LFO {} Sync", index + 1), false),
+
+            division: EnumParam::new(format!("LFO {} Division", index + 1), SyncDivision::Quarter),
+        }
+    }
+}
+
+//
+// Lfo
+//
+
+#[derive(Debug)]
+pub(crate) struct Lfo {
+    oscillator: Oscillator,
+}
+
+impl Lfo {
+    pub(crate) fn new() -> Self {
+        Self {
+            oscillator: Oscillator::new(),
+        }
+    }
+
+    // sync to host transport once per block in tempo-synced mode, otherwise free-running
+    pub(crate) fn update(&mut self, params: &LfoParams, transport: &TransportInfo) {
+        self.oscillator.oscillator_type = params.shape.value();
+        if params.sync.value() {
+            let beats = params.division.value().beats();
+            let tempo = transport.tempo.unwrap_or(120.0);
+            self.oscillator.frequency = (tempo / 60.0 / beats) as f32;
+            if let Some(pos_beats) = transport.pos_beats {
+                self.oscillator.phase = (pos_beats / beats).rem_euclid(1.0) as f32;
+            }
+        } else {
+            self.oscillator.frequency = params.rate.value();
+        }
+    }
+
+    // bipolar value in [-1, 1] (undo the loudness normalization of `Oscillator`)
+    pub(crate) fn next(&mut self, delta: f32, wavetable: &Wavetable) -> f32 {
+        let factor = self.oscillator.oscillator_type.factor();
+        self.oscillator.next(delta, wavetable) / factor
+    }
+}
+
+//
+// modulation matrix
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum ModSource {
+    #[name = "-"]
+    None,
+    #[name = "LFO 1"]
+    Lfo1,
+    #[name = "LFO 2"]
+    Lfo2,
+    Envelope,
+}
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum ModDestination {
+    Pitch,
+    Gain,
+    Pan,
+    Cutoff,
+    #[name = "Wavetable Position"]
+    WavetablePosition,
+}
+
+impl ModDestination {
+    // modulation amount at full depth and full source value
+    fn range(self) -> f32 {
+        match self {
+            // semitones
+            ModDestination::Pitch => 12.0,
+            // relative gain
+            ModDestination::Gain => 1.0,
+            // hard left/right
+            ModDestination::Pan => 1.0,
+            // octaves
+            ModDestination::Cutoff => 4.0,
+            // whole table
+            ModDestination::WavetablePosition => 1.0,
+        }
+    }
+}
+
+#[derive(Params)]
+pub(crate) struct ModSlotParams {
+    #[id = "mod_source"]
+    pub(crate) source: EnumParam<ModSource>,
+
+    #[id = "mod_destination"]
+    pub(crate) destination: EnumParam<ModDestination>,
+
+    #[id = "mod_depth"]
+    pub(crate) depth: FloatParam,
+}
+
+impl ModSlotParams {
+    pub(crate) fn new(index: usize) -> Self {
+        Self {
+            source: EnumParam::new(format!("Mod {} Source", index + 1), ModSource::None),
+
+            destination: EnumParam::new(
+                format!("Mod {} Destination", index + 1),
+                ModDestination::Pitch,
+            ),
+
+            depth: FloatParam::new(
+                format!("Mod {} Depth", index + 1),
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct ModSlot {
+    pub(crate) source: ModSource,
+    pub(crate) destination: ModDestination,
+    pub(crate) depth: f32,
+}
+
+impl ModSlot {
+    pub(crate) fn new() -> Self {
+        Self {
+            source: ModSource::None,
+            destination: ModDestination::Pitch,
+            depth: 0.0,
+        }
+    }
+}
+
+// sum of modulations for each destination
+#[derive(Debug, Default, Copy, Clone)]
+pub(crate) struct ModValues {
+    // semitones
+    pub(crate) pitch: f32,
+    // relative gain offset (applied as `1 + gain`)
+    pub(crate) gain: f32,
+    // offset in [-1, 1]
+    pub(crate) pan: f32,
+    // octaves
+    pub(crate) cutoff: f32,
+    // offset in [-1, 1]
+    pub(crate) wavetable_position: f32,
+}
+
+pub(crate) fn evaluate(slots: &[ModSlot], lfos: &[f32; NUM_LFOS], envelope: f32) -> ModValues {
+    let mut values = ModValues::default();
+    for slot in slots {
+        let source = match slot.source {
+            ModSource::None => continue,
+            ModSource::Lfo1 => lfos[0],
+            ModSource::Lfo2 => lfos[1],
+            ModSource::Envelope => envelope,
+        };
+        let amount = slot.depth * source * slot.destination.range();
+        match slot.destination {
+            ModDestination::Pitch => values.pitch += amount,
+            ModDestination::Gain => values.gain += amount,
+            ModDestination::Pan => values.pan += amount,
+            ModDestination::Cutoff => values.cutoff += amount,
+            ModDestination::WavetablePosition => values.wavetable_position += amount,
+        }
+    }
+    values
+}
@@ -0,0 +1,56 @@
+use crate::Random;
+use nih_plug::prelude::*;
+
+//
+// NoiseType
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum NoiseType {
+    White,
+    // -3 dB/oct
+    Pink,
+}
+
+//
+// Noise
+//
+
+// scale to the same square integral norm as sine (cf. `OscillatorType::norm`)
+// (uniform white noise has rms 1/sqrt(3) and the pink filter below amplifies it to about 1.7)
+const WHITE_FACTOR: f32 = 1.2247449;
+const PINK_FACTOR: f32 = 0.41;
+
+#[derive(Debug)]
+pub(crate) struct Noise {
+    pub(crate) noise_type: NoiseType,
+    // fixed seed so that offline renders are reproducible
+    random: Random,
+    // Paul Kellet's "economy" pinking filter
+    // (cf. https://www.firstpr.com.au/dsp/pink-noise/)
+    pink: [f32; 3],
+}
+
+impl Noise {
+    pub(crate) fn new(seed: u32) -> Self {
+        Self {
+            noise_type: NoiseType::White,
+            random: Random::new(seed),
+            pink: [0.0; 3],
+        }
+    }
+
+    pub(crate) fn next(&mut self) -> f32 {
+        let white = 2.0 * self.random.next_f32() - 1.0;
+        match self.noise_type {
+            NoiseType::White => WHITE_FACTOR * white,
+            NoiseType::Pink => {
+                let [b0, b1, b2] = &mut self.pink;
+                *b0 = 0.99765 * *b0 + 0.0990460 * white;
+                *b1 = 0.96300 * *b1 + 0.2965164 * white;
+                *b2 = 0.57000 * *b2 + 1.0526913 * white;
+                PINK_FACTOR * (*b0 + *b1 + *b2 + 0.1848 * white)
+            }
+        }
+    }
+}
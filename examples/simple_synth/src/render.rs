@@ -0,0 +1,395 @@
+use crate::{MyParams, MyPlugin, TransportInfo};
+use nih_plug::prelude::*;
+use std::path::PathBuf;
+
+// offline rendering without audio/midi backend e.g.
+//
+//   simple_synth render --output out.wav --param oscillator=Sawtooth --param cutoff=800 \
+//     --note 60:0:1 --note 64:0.5:1:0.8
+//
+// where notes are given as `NOTE:START:LENGTH[:VELOCITY]` (start/length in seconds)
+
+const USAGE: &str = "usage: simple_synth render --output <FILE> [--sample-rate <HZ>] [--tempo <BPM>] [--tail <SECONDS>] [--param <ID>=<VALUE>]... [--note <NOTE>:<START>:<LENGTH>[:<VELOCITY>]]...";
+
+const BLOCK_SIZE: usize = 512;
+
+pub fn run(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args)?;
+    let samples = render(&options)?;
+
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: options.sample_rate as u32,
+        bits_per_sample: 32,
+        sample_format: hound::SampleFormat::Float,
+    };
+    let mut writer = hound::WavWriter::create(&options.output, spec).map_err(|e| e.to_string())?;
+    for (left, right) in samples {
+        writer.write_sample(left).map_err(|e| e.to_string())?;
+        writer.write_sample(right).map_err(|e| e.to_string())?;
+    }
+    writer.finalize().map_err(|e| e.to_string())
+}
+
+// stereo samples
+fn render(options: &Options) -> Result<Vec<(f32, f32)>, String> {
+    let mut plugin = MyPlugin::default();
+    apply_params(&plugin.params, &options.params)?;
+
+    // follow the same lifecycle as plugin wrappers
+    let bus_config = BusConfig {
+        num_input_channels: MyPlugin::DEFAULT_INPUT_CHANNELS,
+        num_output_channels: MyPlugin::DEFAULT_OUTPUT_CHANNELS,
+        aux_input_busses: AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        },
+        aux_output_busses: AuxiliaryIOConfig {
+            num_busses: 0,
+            num_channels: 0,
+        },
+    };
+    let buffer_config = BufferConfig {
+        sample_rate: options.sample_rate,
+        min_buffer_size: None,
+        max_buffer_size: BLOCK_SIZE as u32,
+        process_mode: ProcessMode::Offline,
+    };
+    if !plugin.initialize(&bus_config, &buffer_config, &mut OfflineContext) {
+        return Err("failed to initialize plugin".to_string());
+    }
+    plugin.reset();
+
+    // wrappers snap smoothers to the current values on initialization
+    for (_, param, _) in plugin.params.param_map() {
+        // SAFETY: pointers stay valid while `plugin.params` is alive
+        unsafe {
+            match param {
+                ParamPtr::FloatParam(param) => (*param).smoothed.reset((*param).value()),
+                ParamPtr::IntParam(param) => (*param).smoothed.reset((*param).value()),
+                _ => {}
+            }
+        }
+    }
+
+    // note-off before note-on at the same time so that repeated notes retrigger
+    let mut events: Vec<(usize, NoteEvent)> = Vec::new();
+    for note in options.notes.iter() {
+        let start = (note.start * options.sample_rate).round() as usize;
+        let end = ((note.start + note.length) * options.sample_rate).round() as usize;
+        events.push((
+            start,
+            NoteEvent::NoteOn {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: note.note,
+                velocity: note.velocity,
+            },
+        ));
+        events.push((
+            end.max(start + 1),
+            NoteEvent::NoteOff {
+                timing: 0,
+                voice_id: None,
+                channel: 0,
+                note: note.note,
+                velocity: 0.0,
+            },
+        ));
+    }
+    events.sort_by_key(|(time, event)| (*time, matches!(event, NoteEvent::NoteOn { .. })));
+    let mut events = events.into_iter().peekable();
+
+    let end = options
+        .notes
+        .iter()
+        .map(|note| note.start + note.length)
+        .fold(0.0, f32::max);
+    let num_samples = ((end + options.tail) * options.sample_rate).ceil() as usize;
+
+    let mut samples = Vec::with_capacity(num_samples);
+    let mut left = vec![0.0; BLOCK_SIZE];
+    let mut right = vec![0.0; BLOCK_SIZE];
+    let mut block_start = 0;
+    while block_start < num_samples {
+        let block_end = num_samples.min(block_start + BLOCK_SIZE);
+        let block_len = block_end - block_start;
+
+        // transport is playing from the start of the render
+        let seconds = block_start as f64 / options.sample_rate as f64;
+        let transport = TransportInfo {
+            sample_rate: options.sample_rate,
+            tempo: Some(options.tempo),
+            pos_beats: Some(seconds * options.tempo / 60.0),
+        };
+
+        let mut buffer = [&mut left[..block_len], &mut right[..block_len]];
+        plugin.process_block(&mut buffer, &transport, || {
+            events
+                .next_if(|(time, _)| *time < block_end)
+                .map(|(time, event)| with_timing(event, (time - block_start) as u32))
+        });
+
+        samples.extend(
+            left[..block_len]
+                .iter()
+                .copied()
+                .zip(right[..block_len].iter().copied()),
+        );
+        block_start = block_end;
+    }
+
+    plugin.deactivate();
+    Ok(samples)
+}
+
+fn with_timing(event: NoteEvent, timing: u32) -> NoteEvent {
+    match event {
+        NoteEvent::NoteOn {
+            voice_id,
+            channel,
+            note,
+            velocity,
+            ..
+        } => NoteEvent::NoteOn {
+            timing,
+            voice_id,
+            channel,
+            note,
+            velocity,
+        },
+        NoteEvent::NoteOff {
+            voice_id,
+            channel,
+            note,
+            velocity,
+            ..
+        } => NoteEvent::NoteOff {
+            timing,
+            voice_id,
+            channel,
+            note,
+            velocity,
+        },
+        event => event,
+    }
+}
+
+//
+// OfflineContext
+//
+
+struct OfflineContext;
+
+impl InitContext<MyPlugin> for OfflineContext {
+    fn plugin_api(&self) -> PluginApi {
+        PluginApi::Standalone
+    }
+
+    fn execute(&self, _task: ()) {}
+
+    fn set_latency_samples(&self, _samples: u32) {}
+
+    fn set_current_voice_capacity(&self, _capacity: u32) {}
+}
+
+//
+// Options
+//
+
+#[derive(Debug)]
+struct Options {
+    output: PathBuf,
+    sample_rate: f32,
+    tempo: f64,
+    // seconds rendered after the last note-off (for release and filter tails)
+    tail: f32,
+    // parameter id and value
+    params: Vec<(String, String)>,
+    notes: Vec<Note>,
+}
+
+#[derive(Debug)]
+struct Note {
+    note: u8,
+    start: f32,
+    length: f32,
+    velocity: f32,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut output = None;
+        let mut options = Self {
+            output: PathBuf::new(),
+            sample_rate: 44100.0,
+            tempo: 120.0,
+            tail: 1.0,
+            params: vec![],
+            notes: vec![],
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {}\n{}", arg, USAGE))
+            };
+            match arg.as_str() {
+                "--output" => output = Some(PathBuf::from(value()?)),
+                "--sample-rate" => options.sample_rate = parse_number(arg, value()?)?,
+                "--tempo" => options.tempo = parse_number(arg, value()?)?,
+                "--tail" => options.tail = parse_number(arg, value()?)?,
+                "--param" => {
+                    let value = value()?;
+                    let (id, value) = value
+                        .split_once('=')
+                        .ok_or_else(|| format!("invalid --param {} (expected ID=VALUE)", value))?;
+                    options.params.push((id.to_string(), value.to_string()));
+                }
+                "--note" => options.notes.push(Note::parse(value()?)?),
+                _ => return Err(format!("unknown argument {}\n{}", arg, USAGE)),
+            }
+        }
+
+        options.output = output.ok_or_else(|| format!("missing --output\n{}", USAGE))?;
+        if options.sample_rate <= 0.0 || options.tempo <= 0.0 || options.tail < 0.0 {
+            return Err("--sample-rate, --tempo and --tail must be positive".to_string());
+        }
+        Ok(options)
+    }
+}
+
+impl Note {
+    fn parse(spec: &str) -> Result<Self, String> {
+        let invalid = || {
+            format!(
+                "invalid --note {} (expected NOTE:START:LENGTH[:VELOCITY])",
+                spec
+            )
+        };
+        let parts: Vec<&str> = spec.split(':').collect();
+        if !(3..=4).contains(&parts.len()) {
+            return Err(invalid());
+        }
+        let note = Self {
+            note: parts[0].parse().map_err(|_| invalid())?,
+            start: parts[1].parse().map_err(|_| invalid())?,
+            length: parts[2].parse().map_err(|_| invalid())?,
+            velocity: parts
+                .get(3)
+                .map_or(Ok(1.0), |v| v.parse())
+                .map_err(|_| invalid())?,
+        };
+        if note.note > 127 || note.start < 0.0 || note.length < 0.0 {
+            return Err(invalid());
+        }
+        Ok(note)
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {} {}", arg, value))
+}
+
+//
+// apply_params
+//
+
+// values are plain (e.g. seconds for envelope times, 0..1 for percentages) and enums are given by variant name.
+// values outside of the parameter range are clamped.
+fn apply_params(params: &MyParams, overrides: &[(String, String)]) -> Result<(), String> {
+    let param_map = params.param_map();
+    let mut errors = vec![];
+    for (id, value) in overrides {
+        let param = match param_map.iter().find(|(param_id, _, _)| param_id == id) {
+            Some((_, param, _)) => *param,
+            None => {
+                errors.push(format!("unknown parameter {}", id));
+                continue;
+            }
+        };
+        // SAFETY: pointers stay valid while `params` is alive
+        unsafe {
+            match plain_to_normalized(param, value) {
+                Some(normalized) => {
+                    param.set_normalized_value(normalized.clamp(0.0, 1.0));
+                }
+                None => errors.push(format!("invalid value for {}: {}", id, value)),
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
+
+unsafe fn plain_to_normalized(param: ParamPtr, value: &str) -> Option<f32> {
+    match param {
+        ParamPtr::FloatParam(_) => value
+            .parse::<f32>()
+            .ok()
+            .map(|plain| param.preview_normalized(plain)),
+        ParamPtr::IntParam(_) => value
+            .parse::<i32>()
+            .ok()
+            .map(|plain| param.preview_normalized(plain as f32)),
+        ParamPtr::BoolParam(_) => value
+            .parse::<bool>()
+            .ok()
+            .map(|plain| if plain { 1.0 } else { 0.0 }),
+        // variant name (case-insensitive)
+        ParamPtr::EnumParam(_) => {
+            let step_count = param.step_count()?;
+            (0..=step_count)
+                .map(|step| step as f32 / step_count as f32)
+                .find(|&normalized| {
+                    param
+                        .normalized_value_to_string(normalized, false)
+                        .eq_ignore_ascii_case(value)
+                })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        Options::parse(&args).unwrap()
+    }
+
+    // noise and random unison phases are seeded
+    #[test]
+    fn render_is_reproducible() {
+        let options = options(&[
+            "--output",
+            "unused.wav",
+            "--tail",
+            "0.1",
+            "--param",
+            "unison=4",
+            "--param",
+            "noise_level=0.5",
+            "--param",
+            "noise_type=Pink",
+            "--note",
+            "60:0:0.2",
+            "--note",
+            "64:0.1:0.2",
+        ]);
+        let first = render(&options).unwrap();
+        let second = render(&options).unwrap();
+        assert!(first
+            .iter()
+            .any(|&(left, right)| left != 0.0 && right != 0.0));
+        assert_eq!(first, second);
+    }
+}
@@ -0,0 +1,582 @@
+use crate::{
+    filter::Filter,
+    modulation::{self, ModSlot, NUM_LFOS},
+    noise::Noise,
+    pan_gains,
+    wavetable::Wavetable,
+    Envelope, EnvelopeStage, Oscillator, Random,
+};
+use nih_plug::prelude::*;
+use std::f32::consts::TAU;
+
+//
+// SynthesisMode
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum SynthesisMode {
+    // oscillators through filter
+    Subtractive,
+    // sine modulator driving phase of the oscillators (then through filter as well)
+    #[name = "FM"]
+    Fm,
+}
+
+//
+// PressureDestination
+//
+
+// where channel/poly pressure (aftertouch) goes
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum PressureDestination {
+    Gain,
+    // filter cutoff
+    Brightness,
+}
+
+// full pressure with full amount
+const PRESSURE_MAX_GAIN: f32 = 1.0;
+const PRESSURE_MAX_OCTAVES: f32 = 4.0;
+
+// smoothing of midi controllers to avoid zipper noise
+pub(crate) const CONTROLLER_SMOOTHING: SmoothingStyle = SmoothingStyle::Linear(10.0);
+
+//
+// RetriggerMode
+//
+
+// how a voice which is still sounding reacts to a new note-on
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum RetriggerMode {
+    // restart oscillator phase and envelope from zero
+    Reset,
+    // keep envelope running while the previous note is held (only pitch changes)
+    Legato,
+    // keep oscillator phase and re-attack from the current envelope level
+    Continue,
+}
+
+//
+// Glide
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum GlideMode {
+    Off,
+    // glide from the previous note on every note-on
+    Always,
+    // glide only while another note is held
+    Legato,
+}
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum GlideType {
+    // same duration regardless of interval
+    #[name = "Constant Time"]
+    ConstantTime,
+    // duration per octave (i.e. wider interval takes longer)
+    #[name = "Constant Rate"]
+    ConstantRate,
+}
+
+// portamento settings applied on note-on
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct Glide {
+    pub(crate) mode: GlideMode,
+    pub(crate) glide_type: GlideType,
+    // seconds (per octave for `GlideType::ConstantRate`)
+    pub(crate) time: f32,
+}
+
+impl Glide {
+    fn is_enabled(self, legato: bool) -> bool {
+        match self.mode {
+            GlideMode::Off => false,
+            GlideMode::Always => true,
+            GlideMode::Legato => legato,
+        }
+    }
+
+    // semitones per second to cover `interval` semitones
+    fn rate(self, interval: f32) -> f32 {
+        match self.glide_type {
+            GlideType::ConstantTime => interval / self.time,
+            GlideType::ConstantRate => 12.0 / self.time,
+        }
+    }
+}
+
+//
+// Voice
+//
+
+pub(crate) const MAX_UNISON: usize = 8;
+
+#[derive(Debug)]
+pub(crate) struct Voice {
+    pub(crate) note: u8,
+    pub(crate) velocity: f32,
+    // frequency before pitch modulation
+    pub(crate) frequency: f32,
+    // remaining portamento in semitones from `frequency` and its speed in semitones per second
+    glide_offset: f32,
+    glide_rate: f32,
+    // only first `unison` oscillators are used
+    pub(crate) oscillators: [Oscillator; MAX_UNISON],
+    pub(crate) unison: usize,
+    // spread between outermost unison oscillators in cents
+    pub(crate) unison_detune: f32,
+    // stereo spread between outermost unison oscillators in [0, 1]
+    pub(crate) unison_spread: f32,
+    // mono sources mixed after unison (before filter and envelope)
+    // with levels smoothed so updated every sample
+    pub(crate) sub_oscillator: Oscillator,
+    pub(crate) sub_octave: i32,
+    pub(crate) sub_level: f32,
+    pub(crate) noise: Noise,
+    pub(crate) noise_level: f32,
+    pub(crate) envelope: Envelope,
+    // left/right
+    pub(crate) filters: [Filter; 2],
+    pub(crate) filter_envelope: Envelope,
+    // filter envelope modulation depth in octaves
+    pub(crate) filter_envelope_amount: f32,
+    pub(crate) synthesis: SynthesisMode,
+    // modulator frequency relative to carrier
+    pub(crate) fm_ratio: f32,
+    // peak phase deviation in radians (smoothed so updated every sample)
+    pub(crate) fm_index: f32,
+    pub(crate) fm_modulator: Oscillator,
+    pub(crate) fm_envelope: Envelope,
+    // pitch bend and vibrato in semitones (smoothed so updated every sample)
+    pub(crate) pitch_offset: f32,
+    // in [0, 1] (smoothed so updated every sample)
+    pub(crate) channel_pressure: f32,
+    pub(crate) poly_pressure: Smoother<f32>,
+    pub(crate) pressure_destination: PressureDestination,
+    pub(crate) pressure_amount: f32,
+    // in [-1, 1] (smoothed so updated every sample)
+    pub(crate) pan: f32,
+    // in [0, 1] (smoothed so updated every sample)
+    pub(crate) pan_spread: f32,
+    // random per note in [-1, 1] scaled by `pan_spread`
+    pan_position: f32,
+    // note-on order used to find the oldest voice when stealing
+    age: u64,
+}
+
+impl Voice {
+    fn new(index: usize) -> Self {
+        Self {
+            note: 0,
+            velocity: 0.0,
+            frequency: 440.0,
+            glide_offset: 0.0,
+            glide_rate: 0.0,
+            oscillators: std::array::from_fn(|_| Oscillator::new()),
+            unison: 1,
+            unison_detune: 0.0,
+            unison_spread: 0.0,
+            sub_oscillator: Oscillator::new(),
+            sub_octave: 1,
+            sub_level: 0.0,
+            noise: Noise::new(0x5EED + index as u32),
+            noise_level: 0.0,
+            envelope: Envelope::new(),
+            filters: [Filter::new(), Filter::new()],
+            filter_envelope: Envelope::new(),
+            filter_envelope_amount: 0.0,
+            synthesis: SynthesisMode::Subtractive,
+            fm_ratio: 1.0,
+            fm_index: 0.0,
+            fm_modulator: Oscillator::new(),
+            fm_envelope: Envelope::new(),
+            pitch_offset: 0.0,
+            channel_pressure: 0.0,
+            poly_pressure: Smoother::new(CONTROLLER_SMOOTHING),
+            pressure_destination: PressureDestination::Gain,
+            pressure_amount: 0.0,
+            pan: 0.0,
+            pan_spread: 0.0,
+            pan_position: 0.0,
+            age: 0,
+        }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        !matches!(self.envelope.stage, EnvelopeStage::Off)
+    }
+
+    pub(crate) fn is_released(&self) -> bool {
+        matches!(
+            self.envelope.stage,
+            EnvelopeStage::Off | EnvelopeStage::Release(_)
+        )
+    }
+
+    // including portamento in progress (but not modulation)
+    fn current_frequency(&self) -> f32 {
+        self.frequency * (self.glide_offset / 12.0).exp2()
+    }
+
+    // randomize phase of unison oscillators to avoid phasing at note start
+    // (single oscillator always starts from zero)
+    fn reset_phases(&mut self, random: &mut Random) {
+        for oscillator in self.oscillators.iter_mut() {
+            oscillator.phase = if self.unison > 1 {
+                random.next_f32()
+            } else {
+                0.0
+            };
+        }
+        self.fm_modulator.phase = 0.0;
+        self.sub_oscillator.phase = 0.0;
+    }
+
+    fn reset(&mut self) {
+        self.envelope.reset();
+        self.filter_envelope.reset();
+        self.fm_envelope.reset();
+    }
+
+    fn trigger(&mut self) {
+        self.envelope.trigger();
+        self.filter_envelope.trigger();
+        self.fm_envelope.trigger();
+    }
+
+    fn release(&mut self) {
+        self.envelope.release();
+        self.filter_envelope.release();
+        self.fm_envelope.release();
+    }
+
+    // returns stereo pair
+    pub(crate) fn next(
+        &mut self,
+        delta: f32,
+        cutoff: f32,
+        wavetable: &Wavetable,
+        wavetable_position: f32,
+        lfos: &[f32; NUM_LFOS],
+        mod_slots: &[ModSlot],
+    ) -> (f32, f32) {
+        let envelope = self.envelope.next(delta);
+        let filter_envelope = self.filter_envelope.next(delta);
+        let fm_envelope = self.fm_envelope.next(delta);
+        let mod_values = modulation::evaluate(mod_slots, lfos, envelope);
+
+        // approach the note linearly in pitch
+        let step = self.glide_rate * delta;
+        self.glide_offset = if self.glide_offset.abs() <= step {
+            0.0
+        } else {
+            self.glide_offset - step * self.glide_offset.signum()
+        };
+
+        let frequency = self.frequency
+            * ((mod_values.pitch + self.pitch_offset + self.glide_offset) / 12.0).exp2();
+        let wavetable_position =
+            (wavetable_position + mod_values.wavetable_position).clamp(0.0, 1.0);
+
+        // phase offset in cycles shared by all unison oscillators
+        let phase_modulation = match self.synthesis {
+            SynthesisMode::Subtractive => 0.0,
+            SynthesisMode::Fm => {
+                self.fm_modulator.frequency = frequency * self.fm_ratio;
+                // modulator stays `OscillatorType::Sine`
+                let value = self.fm_modulator.next(delta, wavetable);
+                self.fm_index / TAU * fm_envelope * value
+            }
+        };
+
+        // unison oscillators spread evenly over detune/stereo range
+        let mut left = 0.0;
+        let mut right = 0.0;
+        for (index, oscillator) in self.oscillators[..self.unison].iter_mut().enumerate() {
+            let offset = unison_offset(index, self.unison);
+            let cents = 0.5 * offset * self.unison_detune;
+            oscillator.frequency = frequency * (cents / 1200.0).exp2();
+            oscillator.wavetable_position = wavetable_position;
+            oscillator.phase_modulation = phase_modulation;
+            let value = oscillator.next(delta, wavetable);
+            let (gain_left, gain_right) = pan_gains(offset * self.unison_spread);
+            left += gain_left * value;
+            right += gain_right * value;
+        }
+
+        // normalize by square integral norm as `OscillatorType::factor` assuming uncorrelated oscillators
+        let norm = (self.unison as f32).sqrt().recip();
+        let left = norm * left;
+        let right = norm * right;
+
+        self.sub_oscillator.frequency = frequency * (-self.sub_octave as f32).exp2();
+        self.sub_oscillator.wavetable_position = wavetable_position;
+        let sub = self.sub_oscillator.next(delta, wavetable);
+        let noise = self.noise.next();
+        let mono = self.sub_level * sub + self.noise_level * noise;
+        let left = left + mono;
+        let right = right + mono;
+
+        // channel and poly pressure don't add up (e.g. when controller sends both)
+        let pressure = self.channel_pressure.max(self.poly_pressure.next()) * self.pressure_amount;
+        let (pressure_gain, pressure_octaves) = match self.pressure_destination {
+            PressureDestination::Gain => (PRESSURE_MAX_GAIN * pressure, 0.0),
+            PressureDestination::Brightness => (0.0, PRESSURE_MAX_OCTAVES * pressure),
+        };
+
+        let cutoff_octaves =
+            self.filter_envelope_amount * filter_envelope + mod_values.cutoff + pressure_octaves;
+        let cutoff = cutoff * cutoff_octaves.exp2();
+        let left = self.filters[0].next(left, cutoff, delta);
+        let right = self.filters[1].next(right, cutoff, delta);
+
+        let gain = (1.0 + mod_values.gain + pressure_gain).max(0.0);
+        let amplitude = self.velocity * envelope * gain;
+        let pan = self.pan + self.pan_spread * self.pan_position + mod_values.pan;
+        let (gain_left, gain_right) = pan_gains(pan);
+        (gain_left * amplitude * left, gain_right * amplitude * right)
+    }
+}
+
+// position of unison oscillator in [-1, 1]
+fn unison_offset(index: usize, unison: usize) -> f32 {
+    if unison <= 1 {
+        return 0.0;
+    }
+    2.0 * (index as f32) / ((unison - 1) as f32) - 1.0
+}
+
+//
+// VoicePool
+//
+
+// fixed number of voices allocated upfront so that `process` doesn't allocate
+pub(crate) const MAX_POLYPHONY: usize = 16;
+
+#[derive(Debug)]
+pub(crate) struct VoicePool {
+    voices: Vec<Voice>,
+    counter: u64,
+    random: Random,
+    // most recently triggered voice where the next glide starts from
+    last_voice: Option<usize>,
+}
+
+impl VoicePool {
+    pub(crate) fn new() -> Self {
+        Self {
+            voices: (0..MAX_POLYPHONY).map(Voice::new).collect(),
+            counter: 0,
+            random: Random::new(0x5EED),
+            last_voice: None,
+        }
+    }
+
+    pub(crate) fn voices_mut(&mut self) -> impl Iterator<Item = &mut Voice> {
+        self.voices.iter_mut()
+    }
+
+    pub(crate) fn active_voices_mut(&mut self) -> impl Iterator<Item = &mut Voice> {
+        self.voices.iter_mut().filter(|voice| voice.is_active())
+    }
+
+    pub(crate) fn note_on(
+        &mut self,
+        note: u8,
+        frequency: f32,
+        velocity: f32,
+        polyphony: usize,
+        retrigger: RetriggerMode,
+        glide: Glide,
+    ) {
+        let polyphony = polyphony.clamp(1, MAX_POLYPHONY);
+
+        // glide from the pitch where the previous note currently is (either in mono or poly)
+        let legato = self
+            .voices
+            .iter()
+            .any(|voice| voice.is_active() && !voice.is_released());
+        let glide_from = self
+            .last_voice
+            .map(|index| self.voices[index].current_frequency())
+            .filter(|_| glide.is_enabled(legato) && glide.time > 0.0);
+
+        // release voices exceeding current polyphony (e.g. when the parameter is lowered while playing)
+        for voice in self.voices[polyphony..].iter_mut() {
+            if !voice.is_released() {
+                voice.release();
+            }
+        }
+
+        // reuse the voice playing the same note, then a free voice, otherwise steal one
+        let voices = &self.voices[..polyphony];
+        let index = voices
+            .iter()
+            .position(|voice| voice.is_active() && voice.note == note)
+            .or_else(|| voices.iter().position(|voice| !voice.is_active()))
+            .unwrap_or_else(|| Self::steal(voices));
+
+        self.counter += 1;
+        let voice = &mut self.voices[index];
+        voice.note = note;
+        voice.frequency = frequency;
+        voice.velocity = velocity;
+        voice.age = self.counter;
+        voice.poly_pressure.reset(0.0);
+        voice.glide_offset = glide_from.map_or(0.0, |from| 12.0 * (from / frequency).log2());
+        voice.glide_rate = glide.rate(voice.glide_offset.abs());
+        self.last_voice = Some(index);
+        if !voice.is_active() {
+            voice.reset_phases(&mut self.random);
+            voice.pan_position = 2.0 * self.random.next_f32() - 1.0;
+            for filter in voice.filters.iter_mut() {
+                filter.reset();
+            }
+            voice.reset();
+            return;
+        }
+        match retrigger {
+            RetriggerMode::Reset => {
+                voice.reset_phases(&mut self.random);
+                voice.pan_position = 2.0 * self.random.next_f32() - 1.0;
+                voice.reset();
+            }
+            RetriggerMode::Legato if !voice.is_released() => {}
+            _ => {
+                voice.trigger();
+            }
+        }
+    }
+
+    pub(crate) fn poly_pressure(&mut self, note: u8, pressure: f32, sample_rate: f32) {
+        for voice in self.voices.iter_mut() {
+            if voice.note == note && voice.is_active() {
+                voice.poly_pressure.set_target(sample_rate, pressure);
+            }
+        }
+    }
+
+    pub(crate) fn note_off(&mut self, note: u8) {
+        for voice in self.voices.iter_mut() {
+            if voice.note == note && !voice.is_released() {
+                voice.release();
+            }
+        }
+    }
+
+    pub(crate) fn release_all(&mut self) {
+        for voice in self.voices.iter_mut() {
+            if !voice.is_released() {
+                voice.release();
+            }
+        }
+    }
+
+    // prefer the quietest voice which is already released, otherwise the oldest one
+    fn steal(voices: &[Voice]) -> usize {
+        let released = voices
+            .iter()
+            .enumerate()
+            .filter(|(_, voice)| voice.is_released())
+            .min_by(|(_, a), (_, b)| a.envelope.level.total_cmp(&b.envelope.level))
+            .map(|(index, _)| index);
+        released.unwrap_or_else(|| {
+            voices
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, voice)| voice.age)
+                .map_or(0, |(index, _)| index)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+
+    const GLIDE_OFF: Glide = Glide {
+        mode: GlideMode::Off,
+        glide_type: GlideType::ConstantTime,
+        time: 0.1,
+    };
+
+    fn render(voice: &mut Voice, wavetable: &Wavetable) -> (f32, f32) {
+        voice.next(
+            1.0 / SAMPLE_RATE,
+            20000.0,
+            wavetable,
+            0.0,
+            &[0.0; NUM_LFOS],
+            &[],
+        )
+    }
+
+    #[test]
+    fn lowering_polyphony_keeps_idle_voices_silent() {
+        let wavetable = Wavetable::sine();
+        let mut pool = VoicePool::new();
+        for note in [60, 62, 64, 65] {
+            pool.note_on(note, 440.0, 1.0, 4, RetriggerMode::Reset, GLIDE_OFF);
+        }
+        for _ in 0..1000 {
+            for voice in pool.active_voices_mut() {
+                render(voice, &wavetable);
+            }
+        }
+
+        // voices above the new limit are released (idle ones stay idle) and the oldest one is stolen
+        pool.note_on(67, 440.0, 1.0, 2, RetriggerMode::Reset, GLIDE_OFF);
+        assert!(pool.voices[2..4].iter().all(|voice| voice.is_released()));
+        assert!(pool.voices[4..].iter().all(|voice| !voice.is_active()));
+
+        // well past the release time (0.1 sec by default)
+        let mut energies = [0.0; MAX_POLYPHONY];
+        for _ in 0..(SAMPLE_RATE as usize) {
+            for (energy, voice) in energies.iter_mut().zip(pool.voices.iter_mut()) {
+                if voice.is_active() {
+                    let (left, right) = render(voice, &wavetable);
+                    *energy += left * left + right * right;
+                }
+            }
+        }
+        for (index, voice) in pool.voices.iter_mut().enumerate() {
+            if index < 2 {
+                assert!(voice.is_active() && !voice.is_released(), "{}", index);
+                assert!(energies[index] > 0.0, "{}", index);
+            } else {
+                assert!(!voice.is_active(), "{}", index);
+                assert_eq!(render(voice, &wavetable), (0.0, 0.0), "{}", index);
+            }
+        }
+    }
+
+    #[test]
+    fn pan_spread_places_notes_apart() {
+        let wavetable = Wavetable::sine();
+        let mut pool = VoicePool::new();
+        for note in [60, 64] {
+            pool.note_on(note, 440.0, 1.0, 4, RetriggerMode::Reset, GLIDE_OFF);
+        }
+        let mut balances = vec![];
+        for spread in [0.0, 1.0] {
+            for voice in pool.voices[..2].iter_mut() {
+                voice.pan_spread = spread;
+                let (mut left, mut right) = (0.0, 0.0);
+                for _ in 0..1000 {
+                    let (l, r) = render(voice, &wavetable);
+                    left += l * l;
+                    right += r * r;
+                }
+                balances.push(left / (left + right));
+            }
+        }
+
+        // centered without spread, otherwise each note has its own position
+        assert!((balances[0] - 0.5).abs() < 1e-3, "{:?}", balances);
+        assert!((balances[1] - 0.5).abs() < 1e-3, "{:?}", balances);
+        assert!((balances[2] - balances[3]).abs() > 1e-2, "{:?}", balances);
+    }
+}
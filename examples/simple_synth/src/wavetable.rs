@@ -0,0 +1,187 @@
+use std::{
+    f32::consts::TAU,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+// Serum-style frame length
+pub(crate) const FRAME_SIZE: usize = 2048;
+
+// arbitrary limit to keep memory bounded (Serum also uses 256)
+const MAX_FRAMES: usize = 256;
+
+//
+// Wavetable
+//
+
+// frames of single-cycle waveforms played back by `OscillatorType::Wavetable`
+// (no mipmapping, so high notes alias with bright tables)
+#[derive(Debug)]
+pub(crate) struct Wavetable {
+    pub(crate) name: String,
+    // `num_frames * FRAME_SIZE` samples
+    samples: Vec<f32>,
+    num_frames: usize,
+}
+
+impl Wavetable {
+    // single sine frame used until a file is loaded
+    pub(crate) fn sine() -> Self {
+        let samples: Vec<f32> = (0..FRAME_SIZE)
+            .map(|index| (TAU * index as f32 / FRAME_SIZE as f32).sin())
+            .collect();
+        Self::from_samples("Sine".to_string(), &samples)
+    }
+
+    // blocking, so call it off the audio thread
+    pub(crate) fn load(path: &Path) -> Result<Self, String> {
+        let mut reader = hound::WavReader::open(path).map_err(|e| e.to_string())?;
+        let spec = reader.spec();
+        let channels = spec.channels as usize;
+
+        // take the first channel only
+        let samples: Vec<f32> = match spec.sample_format {
+            hound::SampleFormat::Float => reader
+                .samples::<f32>()
+                .step_by(channels)
+                .collect::<Result<_, _>>(),
+            hound::SampleFormat::Int => {
+                let scale = (1u32 << (spec.bits_per_sample - 1)) as f32;
+                reader
+                    .samples::<i32>()
+                    .step_by(channels)
+                    .map(|sample| sample.map(|sample| sample as f32 / scale))
+                    .collect::<Result<_, _>>()
+            }
+        }
+        .map_err(|e| e.to_string())?;
+
+        if samples.is_empty() {
+            return Err("empty file".to_string());
+        }
+
+        let name = path
+            .file_name()
+            .map_or("".to_string(), |name| name.to_string_lossy().to_string());
+        Ok(Self::from_samples(name, &samples))
+    }
+
+    // multiple of `FRAME_SIZE` is split into frames, otherwise the whole file is taken as a single cycle
+    fn from_samples(name: String, input: &[f32]) -> Self {
+        let mut samples = if input.len() % FRAME_SIZE == 0 {
+            input[..(input.len().min(MAX_FRAMES * FRAME_SIZE))].to_vec()
+        } else {
+            // resample single cycle of arbitrary length by linear interpolation
+            (0..FRAME_SIZE)
+                .map(|index| {
+                    let position = (index * input.len()) as f32 / FRAME_SIZE as f32;
+                    let i = position as usize;
+                    let t = position - i as f32;
+                    let a = input[i];
+                    let b = input[(i + 1) % input.len()];
+                    a + t * (b - a)
+                })
+                .collect()
+        };
+
+        // remove dc and normalize to the same square integral norm as sine (cf. `OscillatorType::norm`)
+        // so that switching waveforms doesn't jump in loudness
+        let num_frames = samples.len() / FRAME_SIZE;
+        for frame in samples.chunks_mut(FRAME_SIZE) {
+            let mean = frame.iter().sum::<f32>() / FRAME_SIZE as f32;
+            for sample in frame.iter_mut() {
+                *sample -= mean;
+            }
+        }
+        let mean_square = samples.iter().map(|x| x * x).sum::<f32>() / samples.len() as f32;
+        if mean_square > 0.0 {
+            let scale = (0.5 / mean_square).sqrt();
+            for sample in samples.iter_mut() {
+                *sample *= scale;
+            }
+        }
+
+        Self {
+            name,
+            samples,
+            num_frames,
+        }
+    }
+
+    pub(crate) fn num_frames(&self) -> usize {
+        self.num_frames
+    }
+
+    // `position` in [0, 1] morphs over frames and `phase` in [0, 1) goes through a frame,
+    // both linearly interpolated
+    pub(crate) fn value(&self, position: f32, phase: f32) -> f32 {
+        let frame_position = position.clamp(0.0, 1.0) * (self.num_frames - 1) as f32;
+        let frame = (frame_position as usize).min(self.num_frames - 1);
+        let next_frame = (frame + 1).min(self.num_frames - 1);
+        let frame_t = frame_position - frame as f32;
+
+        let sample_position = phase * FRAME_SIZE as f32;
+        let index = (sample_position as usize) % FRAME_SIZE;
+        let next_index = (index + 1) % FRAME_SIZE;
+        let t = sample_position - sample_position.floor();
+
+        let at = |frame: usize| {
+            let a = self.samples[frame * FRAME_SIZE + index];
+            let b = self.samples[frame * FRAME_SIZE + next_index];
+            a + t * (b - a)
+        };
+        let a = at(frame);
+        let b = at(next_frame);
+        a + frame_t * (b - a)
+    }
+}
+
+//
+// WavetableBank
+//
+
+// loaded tables shared between gui thread (loading/selection) and audio thread (playback)
+#[derive(Debug)]
+pub(crate) struct WavetableBank {
+    // every loaded table is kept here so that audio thread never drops the last reference (i.e. never deallocates)
+    tables: Mutex<Vec<Arc<Wavetable>>>,
+    selected: Mutex<Arc<Wavetable>>,
+}
+
+impl WavetableBank {
+    pub(crate) fn new() -> Self {
+        let sine = Arc::new(Wavetable::sine());
+        Self {
+            tables: Mutex::new(vec![sine.clone()]),
+            selected: Mutex::new(sine),
+        }
+    }
+
+    // gui thread
+    pub(crate) fn add(&self, table: Wavetable) {
+        let table = Arc::new(table);
+        self.tables.lock().unwrap().push(table.clone());
+        *self.selected.lock().unwrap() = table;
+    }
+
+    pub(crate) fn select(&self, table: &Arc<Wavetable>) {
+        *self.selected.lock().unwrap() = table.clone();
+    }
+
+    pub(crate) fn tables(&self) -> Vec<Arc<Wavetable>> {
+        self.tables.lock().unwrap().clone()
+    }
+
+    pub(crate) fn selected(&self) -> Arc<Wavetable> {
+        self.selected.lock().unwrap().clone()
+    }
+
+    // audio thread (keep the current table if gui is holding the lock)
+    pub(crate) fn try_update(&self, current: &mut Arc<Wavetable>) {
+        if let Ok(selected) = self.selected.try_lock() {
+            if !Arc::ptr_eq(current, &selected) {
+                *current = selected.clone();
+            }
+        }
+    }
+}
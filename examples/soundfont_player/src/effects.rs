@@ -0,0 +1,241 @@
+use crate::combo_box_for_enum_param;
+use nih_plug::prelude::*;
+use nih_plug_egui::{egui, widgets};
+
+// built-in reverb/chorus of `oxisynth::Synth` (defaults follow fluidsynth)
+
+//
+// ChorusWaveform
+//
+
+#[derive(nih_plug::params::enums::Enum, PartialEq, Debug, Copy, Clone)]
+pub(crate) enum ChorusWaveform {
+    Sine,
+    Triangle,
+}
+
+impl ChorusWaveform {
+    fn mode(self) -> oxisynth::ChorusMode {
+        match self {
+            ChorusWaveform::Sine => oxisynth::ChorusMode::Sine,
+            ChorusWaveform::Triangle => oxisynth::ChorusMode::Triangle,
+        }
+    }
+}
+
+//
+// EffectsParams
+//
+
+#[derive(Params)]
+pub(crate) struct EffectsParams {
+    #[id = "reverb_room_size"]
+    pub(crate) reverb_room_size: FloatParam,
+
+    #[id = "reverb_damping"]
+    pub(crate) reverb_damping: FloatParam,
+
+    #[id = "reverb_width"]
+    pub(crate) reverb_width: FloatParam,
+
+    #[id = "reverb_level"]
+    pub(crate) reverb_level: FloatParam,
+
+    #[id = "chorus_voices"]
+    pub(crate) chorus_voices: IntParam,
+
+    #[id = "chorus_level"]
+    pub(crate) chorus_level: FloatParam,
+
+    #[id = "chorus_speed"]
+    pub(crate) chorus_speed: FloatParam,
+
+    #[id = "chorus_depth"]
+    pub(crate) chorus_depth: FloatParam,
+
+    #[id = "chorus_waveform"]
+    pub(crate) chorus_waveform: EnumParam<ChorusWaveform>,
+}
+
+impl Default for EffectsParams {
+    fn default() -> Self {
+        Self {
+            reverb_room_size: FloatParam::new(
+                "Reverb Room Size",
+                0.2,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_step_size(0.01),
+
+            reverb_damping: FloatParam::new(
+                "Reverb Damping",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_step_size(0.01),
+
+            reverb_width: FloatParam::new(
+                "Reverb Width",
+                0.5,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 100.0,
+                },
+            )
+            .with_step_size(0.1),
+
+            reverb_level: FloatParam::new(
+                "Reverb Level",
+                0.9,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_step_size(0.01),
+
+            chorus_voices: IntParam::new("Chorus Voices", 3, IntRange::Linear { min: 0, max: 99 }),
+
+            chorus_level: FloatParam::new(
+                "Chorus Level",
+                2.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 10.0,
+                },
+            )
+            .with_step_size(0.01),
+
+            chorus_speed: FloatParam::new(
+                "Chorus Speed",
+                0.3,
+                FloatRange::Skewed {
+                    min: 0.29,
+                    max: 5.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_unit(" Hz")
+            .with_step_size(0.01),
+
+            chorus_depth: FloatParam::new(
+                "Chorus Depth",
+                8.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 21.0,
+                },
+            )
+            .with_unit(" ms")
+            .with_step_size(0.1),
+
+            chorus_waveform: EnumParam::new("Chorus Waveform", ChorusWaveform::Sine),
+        }
+    }
+}
+
+//
+// gui (rows of 2-column grid)
+//
+
+pub(crate) fn effects_ui(ui: &mut egui::Ui, params: &EffectsParams, setter: &ParamSetter) {
+    ui.label("Reverb Room Size");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.reverb_room_size,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Reverb Damping");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.reverb_damping,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Reverb Width");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.reverb_width,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Reverb Level");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.reverb_level,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Chorus Voices");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.chorus_voices,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Chorus Level");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.chorus_level,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Chorus Speed");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.chorus_speed,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Chorus Depth");
+    ui.add(widgets::ParamSlider::for_param(
+        &params.chorus_depth,
+        setter,
+    ));
+    ui.end_row();
+
+    ui.label("Chorus Waveform");
+    combo_box_for_enum_param(
+        egui::ComboBox::from_id_source("chorus_waveform"),
+        ui,
+        &params.chorus_waveform,
+        setter,
+    );
+    ui.end_row();
+}
+
+//
+// EffectsSettings
+//
+
+// snapshot of params to forward to `Synth` only when changed
+#[derive(PartialEq, Debug, Copy, Clone)]
+pub(crate) struct EffectsSettings {
+    reverb: (f32, f32, f32, f32),
+    chorus: (u32, f32, f32, f32, ChorusWaveform),
+}
+
+impl EffectsSettings {
+    pub(crate) fn new(params: &EffectsParams) -> Self {
+        Self {
+            reverb: (
+                params.reverb_room_size.value(),
+                params.reverb_damping.value(),
+                params.reverb_width.value(),
+                params.reverb_level.value(),
+            ),
+            chorus: (
+                params.chorus_voices.value() as u32,
+                params.chorus_level.value(),
+                params.chorus_speed.value(),
+                params.chorus_depth.value(),
+                params.chorus_waveform.value(),
+            ),
+        }
+    }
+
+    pub(crate) fn apply(&self, synth: &mut oxisynth::Synth) {
+        let (room_size, damping, width, level) = self.reverb;
+        synth.set_reverb_params(room_size, damping, width, level);
+        let (voices, level, speed, depth, waveform) = self.chorus;
+        synth.set_chorus_params(voices, level, speed, depth, waveform.mode());
+    }
+}
@@ -1,15 +1,45 @@
+use analyzer::{AnalyzerView, SampleRing};
+use atomic_float::AtomicF32;
+use effects::{effects_ui, EffectsParams, EffectsSettings};
 use nih_plug::prelude::*;
 use nih_plug_egui::{create_egui_editor, egui, widgets, EguiState};
+use serde::{Deserialize, Serialize};
 use std::{
-    ffi::OsString,
-    sync::{Arc, Mutex},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, Arc, Mutex},
 };
+use synth_slot::SynthSlot;
+
+mod effects;
+mod synth_slot;
 
 pub struct MyPlugin {
     params: Arc<MyParams>,
-    synth: Arc<Mutex<oxisynth::Synth>>,
+    // owned by audio thread and replaced via `synth_slot` on soundfont/preset change
+    synth: Box<oxisynth::Synth>,
+    synth_slot: Arc<SynthSlot>,
+    // replaced synth is crossfaded out to avoid clicks
+    fading_synth: Option<Box<oxisynth::Synth>>,
+    fade_remaining: u32,
+    fade_length: u32,
+    // last effects forwarded to `synth` (`None` to forward on next process)
+    effects_settings: Option<EffectsSettings>,
+    // events of the current buffer (preallocated so that `process` doesn't allocate)
+    events: Vec<NoteEvent>,
+    // to prepare new synth off the audio thread
+    sample_rate: Arc<AtomicF32>,
+    analyzer_ring: Arc<SampleRing>,
+    // loading soundfont files either from gui or on state restore
+    soundfont_promise: Arc<Mutex<Option<poll_promise::Promise<Option<()>>>>>,
 }
 
+// midi channels with independent preset selection
+const NUM_CHANNELS: usize = 16;
+
+const CROSSFADE_MS: f32 = 20.0;
+
+const MAX_EVENTS: usize = 1024;
+
 // embed 1KB of simple soundfont as default fallback
 const DEFAULT_SOUNDFONT_BYTES: &[u8] =
     include_bytes!("../../../thirdparty/OxiSynth/testdata/sin.sf2");
@@ -30,12 +60,52 @@ struct MyParams {
     #[id = "gain"]
     gain: FloatParam,
 
+    #[nested(group = "Effects")]
+    effects: EffectsParams,
+
     // keep soundfont related states independently from `Synth` only for the used on gui thread
-    // TODO: persist?
+    // (only file paths are saved and files are reloaded by `MyPlugin::reload_soundfonts`)
     // TODO: Arc<Mutex<...>> looks too verbose when we know these are only accessed on main thread
-    soundfonts: Arc<Mutex<Vec<(String, OsString, oxisynth::SoundFont)>>>,
-    soundfont: Arc<Mutex<Option<(String, OsString, oxisynth::SoundFont)>>>,
-    preset: Arc<Mutex<Option<(u32, u32, String)>>>,
+    #[persist = "soundfonts"]
+    soundfonts: Arc<Mutex<Vec<SoundfontFile>>>,
+    #[persist = "soundfont"]
+    soundfont: Arc<Mutex<Option<PathBuf>>>,
+    // for each midi channel
+    #[persist = "presets"]
+    presets: Arc<Mutex<[Option<(u32, u32, String)>; NUM_CHANNELS]>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SoundfontFile {
+    name: String,
+    path: PathBuf,
+    #[serde(skip)]
+    status: SoundfontStatus,
+}
+
+#[derive(Default)]
+enum SoundfontStatus {
+    // restored from state and not yet reloaded
+    #[default]
+    NotLoaded,
+    Loaded(oxisynth::SoundFont),
+    // file is gone or broken (kept so that saving the session again doesn't lose it)
+    Missing(String),
+}
+
+impl SoundfontFile {
+    // blocking, so call it off the audio thread
+    fn load(path: &Path) -> Result<oxisynth::SoundFont, String> {
+        let mut file = std::fs::File::open(path).map_err(|e| e.to_string())?;
+        oxisynth::SoundFont::load(&mut file).map_err(|_| "invalid soundfont file".to_string())
+    }
+
+    fn soundfont(&self) -> Option<&oxisynth::SoundFont> {
+        match &self.status {
+            SoundfontStatus::Loaded(soundfont) => Some(soundfont),
+            _ => None,
+        }
+    }
 }
 
 impl Default for MyPlugin {
@@ -44,7 +114,16 @@ impl Default for MyPlugin {
         synth.add_font(DEFAULT_SOUNDFONT.clone(), true);
         Self {
             params: Arc::new(MyParams::default()),
-            synth: Arc::new(Mutex::new(synth)),
+            synth: Box::new(synth),
+            synth_slot: Default::default(),
+            fading_synth: None,
+            fade_remaining: 0,
+            fade_length: 1,
+            effects_settings: None,
+            events: Vec::with_capacity(MAX_EVENTS),
+            sample_rate: Arc::new(AtomicF32::new(44100.0)),
+            analyzer_ring: Arc::new(SampleRing::new(4096)),
+            soundfont_promise: Default::default(),
         }
     }
 }
@@ -52,7 +131,7 @@ impl Default for MyPlugin {
 impl Default for MyParams {
     fn default() -> Self {
         Self {
-            editor_state: EguiState::from_size(450, 300),
+            editor_state: EguiState::from_size(450, 640),
 
             gain: FloatParam::new(
                 "Gain",
@@ -68,9 +147,11 @@ impl Default for MyParams {
             .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
             .with_string_to_value(formatters::s2v_f32_gain_to_db()),
 
+            effects: EffectsParams::default(),
+
             soundfonts: Arc::new(Mutex::new(vec![])),
             soundfont: Arc::new(Mutex::new(None)),
-            preset: Arc::new(Mutex::new(None)),
+            presets: Arc::new(Mutex::new(Default::default())),
         }
     }
 }
@@ -100,192 +181,263 @@ impl Plugin for MyPlugin {
         buffer_config: &BufferConfig,
         _context: &mut impl InitContext<Self>,
     ) -> bool {
-        let mut synth = self.synth.lock().unwrap();
-        synth.set_sample_rate(buffer_config.sample_rate);
+        // not on audio thread, so it's fine to take over pending synth and drop old ones here
+        self.synth_slot.collect();
+        if let Some(synth) = self.synth_slot.receive() {
+            self.synth = synth;
+        }
+        self.fading_synth = None;
+        self.effects_settings = None;
+        self.synth.set_sample_rate(buffer_config.sample_rate);
+        self.sample_rate
+            .store(buffer_config.sample_rate, Ordering::Relaxed);
+        self.fade_length = ((buffer_config.sample_rate * CROSSFADE_MS / 1000.0) as u32).max(1);
+        self.analyzer_ring
+            .set_sample_rate(buffer_config.sample_rate);
+
+        // wrappers re-initialize after restoring state
+        self.reload_soundfonts();
         true
     }
 
     fn editor(&self, _async_executor: AsyncExecutor<Self>) -> Option<Box<dyn Editor>> {
         let params = self.params.clone();
-        let synth = self.synth.clone();
-        let soundfont_promise: Arc<Mutex<Option<poll_promise::Promise<Option<()>>>>> =
-            Default::default();
+        let synth_slot = self.synth_slot.clone();
+        let sample_rate = self.sample_rate.clone();
+        let analyzer_ring = self.analyzer_ring.clone();
+        let soundfont_promise = self.soundfont_promise.clone();
         create_egui_editor(
             params.editor_state.clone(),
-            (),
+            AnalyzerView::new(),
             |_, _| {},
-            move |egui_ctx, setter, _state| {
-                // TODO: more settings? (reverb, chorus)
+            move |egui_ctx, setter, analyzer_view| {
                 // TODO: refactor egui routines
+                // drop synth replaced by audio thread
+                synth_slot.collect();
+
                 egui::CentralPanel::default().show(egui_ctx, |ui| {
-                    egui::Grid::new("params")
-                        .num_columns(2)
-                        .spacing([20.0, 4.0])
-                        .show(ui, |ui| {
-                            ui.label("Gain");
-                            ui.add(widgets::ParamSlider::for_param(&params.gain, setter));
-                            ui.end_row();
-
-                            //
-                            // soundfont/bank/patch selector
-                            //
-                            let mut reset_synth = false;
-                            let soundfonts = params.soundfonts.clone();
-                            let mut current_soundfont = params.soundfont.lock().unwrap();
-                            let mut current_preset = params.preset.lock().unwrap();
-
-                            ui.label("Soundfont");
-                            // TODO: egui-baseview doesn't support hyperlink? (though egui-winit does https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/crates/egui-winit/src/lib.rs#L678)
-                            // ui.hyperlink_to("Soundfont", "https://github.com/FluidSynth/fluidsynth/wiki/SoundFont");
-                            ui.horizontal(|ui| {
-                                egui::ComboBox::from_id_source("soundfont")
-                                    .width(200.0)
-                                    .selected_text(current_soundfont.as_ref().map_or("", |v| &v.0))
-                                    .show_ui(ui, |ui| {
-                                        for el in soundfonts.lock().unwrap().iter() {
-                                            let selected = current_soundfont
-                                                .as_ref()
-                                                .map_or(false, |v| v.0 == el.0);
-                                            let mut response =
-                                                ui.selectable_label(selected, el.0.clone());
-                                            if response.clicked() && !selected {
-                                                *current_soundfont = Some(el.clone());
-                                                *current_preset = None;
-                                                reset_synth = true;
-                                                response.mark_changed();
-                                            }
-                                        }
-                                    });
+                    analyzer_view.ui(ui, &analyzer_ring, egui::vec2(ui.available_width(), 120.0));
+
+                    ui.separator();
+
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("params")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                ui.label("Gain");
+                                ui.add(widgets::ParamSlider::for_param(&params.gain, setter));
+                                ui.end_row();
 
                                 //
-                                // file dialog to choose soundfont https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/examples/file_dialog/src/main.rs
-                                // and asynchronous parsing of soundfont https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/examples/download_image/src/main.rs
+                                // soundfont/bank/patch selector
                                 //
-                                let mut soundfont_promise = soundfont_promise.lock().unwrap(); // TODO: instead of spawning thread on its own, it's better to use `async_executor` but that would require more verbose logic to keep track of states
-                                let mut is_loading = false;
-                                let mut is_error = false;
-                                if let Some(soundfont_promise_inner) = soundfont_promise.as_ref() {
-                                    match soundfont_promise_inner.ready() {
-                                        None => {
-                                            is_loading = true;
+                                let mut reset_synth = false;
+                                let soundfonts = params.soundfonts.clone();
+                                let mut current_soundfont = params.soundfont.lock().unwrap();
+                                let mut current_presets = params.presets.lock().unwrap();
+
+                                ui.label("Soundfont");
+                                // TODO: egui-baseview doesn't support hyperlink? (though egui-winit does https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/crates/egui-winit/src/lib.rs#L678)
+                                // ui.hyperlink_to("Soundfont", "https://github.com/FluidSynth/fluidsynth/wiki/SoundFont");
+                                ui.horizontal(|ui| {
+                                    let mut missing = None;
+                                    {
+                                        let soundfonts = soundfonts.lock().unwrap();
+                                        let selected_file = soundfonts.iter().find(|el| {
+                                            current_soundfont.as_ref() == Some(&el.path)
+                                        });
+                                        if let Some(SoundfontFile {
+                                            path,
+                                            status: SoundfontStatus::Missing(error),
+                                            ..
+                                        }) = selected_file
+                                        {
+                                            missing =
+                                                Some(format!("{}\n{}", path.display(), error));
                                         }
-                                        Some(Some(())) => {
-                                            // reset promise on success
-                                            *soundfont_promise = None;
+                                        egui::ComboBox::from_id_source("soundfont")
+                                            .width(200.0)
+                                            .selected_text(selected_file.map_or("", |v| &v.name))
+                                            .show_ui(ui, |ui| {
+                                                for el in soundfonts.iter() {
+                                                    let selected = current_soundfont.as_ref()
+                                                        == Some(&el.path);
+                                                    let label = match el.status {
+                                                        SoundfontStatus::Missing(_) => {
+                                                            format!("{} (missing)", el.name)
+                                                        }
+                                                        _ => el.name.clone(),
+                                                    };
+                                                    let mut response =
+                                                        ui.selectable_label(selected, label);
+                                                    if response.clicked() && !selected {
+                                                        *current_soundfont = Some(el.path.clone());
+                                                        *current_presets = Default::default();
+                                                        reset_synth = true;
+                                                        response.mark_changed();
+                                                    }
+                                                }
+                                            });
+                                    }
+
+                                    //
+                                    // file dialog to choose soundfont https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/examples/file_dialog/src/main.rs
+                                    // and asynchronous parsing of soundfont https://github.com/emilk/egui/blob/34f587d1e1cc69146f7a02f20903e4f573030ffd/examples/download_image/src/main.rs
+                                    //
+                                    let mut soundfont_promise = soundfont_promise.lock().unwrap(); // TODO: instead of spawning thread on its own, it's better to use `async_executor` but that would require more verbose logic to keep track of states
+                                    let mut is_loading = false;
+                                    let mut is_error = false;
+                                    if let Some(soundfont_promise_inner) =
+                                        soundfont_promise.as_ref()
+                                    {
+                                        match soundfont_promise_inner.ready() {
+                                            None => {
+                                                is_loading = true;
+                                            }
+                                            Some(Some(())) => {
+                                                // reset promise on success
+                                                *soundfont_promise = None;
+                                            }
+                                            Some(None) => {
+                                                is_error = true;
+                                            }
                                         }
-                                        Some(None) => {
-                                            is_error = true;
+                                    }
+
+                                    if ui
+                                        .button(if is_loading {
+                                            "Loading…"
+                                        } else {
+                                            "Load File"
+                                        })
+                                        .clicked()
+                                        && !is_loading
+                                    {
+                                        if let Some(path) = rfd::FileDialog::new().pick_file() {
+                                            let soundfonts = soundfonts.clone();
+                                            let promise: poll_promise::Promise<Option<()>> =
+                                                poll_promise::Promise::spawn_thread(
+                                                    "load-soundfont-file",
+                                                    move || {
+                                                        let soundfont =
+                                                            SoundfontFile::load(&path).ok()?;
+                                                        let name = path
+                                                            .file_name()
+                                                            .unwrap()
+                                                            .to_string_lossy()
+                                                            .to_string();
+                                                        let mut soundfonts =
+                                                            soundfonts.lock().unwrap();
+                                                        // loading the same file again replaces it (e.g. after fixing missing file)
+                                                        soundfonts.retain(|el| el.path != path);
+                                                        soundfonts.push(SoundfontFile {
+                                                            name,
+                                                            path,
+                                                            status: SoundfontStatus::Loaded(
+                                                                soundfont,
+                                                            ),
+                                                        });
+                                                        Some(())
+                                                    },
+                                                );
+                                            *soundfont_promise = Some(promise);
                                         }
                                     }
-                                }
 
-                                if ui
-                                    .button(if is_loading {
-                                        "Loading…"
-                                    } else {
-                                        "Load File"
-                                    })
-                                    .clicked()
-                                    && !is_loading
-                                {
-                                    if let Some(path) = rfd::FileDialog::new().pick_file() {
-                                        let promise: poll_promise::Promise<Option<()>> =
-                                            poll_promise::Promise::spawn_thread(
-                                                "load-soundfont-file",
-                                                move || {
-                                                    let mut file =
-                                                        std::fs::File::open(path.clone()).ok()?;
-                                                    let soundfont =
-                                                        oxisynth::SoundFont::load(&mut file)
-                                                            .ok()?;
-                                                    let file_name = path
-                                                        .file_name()
-                                                        .unwrap()
-                                                        .to_string_lossy()
-                                                        .to_string();
-                                                    let path_string =
-                                                        path.as_os_str().to_os_string();
-                                                    soundfonts.lock().unwrap().push((
-                                                        file_name,
-                                                        path_string,
-                                                        soundfont,
-                                                    ));
-                                                    Some(())
-                                                },
-                                            );
-                                        *soundfont_promise = Some(promise);
+                                    if is_error {
+                                        ui.label(
+                                            egui::RichText::new("ERROR").color(egui::Color32::RED),
+                                        );
                                     }
-                                }
 
-                                if is_error {
-                                    ui.label(
-                                        egui::RichText::new("ERROR").color(egui::Color32::RED),
-                                    );
-                                }
-                            });
-                            ui.end_row();
-
-                            ui.label("Preset");
-                            egui::ComboBox::from_id_source("preset")
-                                .width(300.0)
-                                .selected_text(
-                                    current_preset
-                                        .as_ref()
-                                        .map_or("".to_string(), |v| v.2.clone()),
-                                )
-                                .show_ui(ui, |ui| {
-                                    if let Some((_, _, soundfont)) = current_soundfont.as_ref() {
-                                        for preset in soundfont.presets.iter() {
-                                            let formatted = format!(
-                                                "{} - {}   {}",
-                                                preset.banknum(),
-                                                preset.num(),
-                                                preset.name()
-                                            );
-                                            let selected = current_preset
+                                    if let Some(missing) = missing {
+                                        ui.label(
+                                            egui::RichText::new("missing soundfont")
+                                                .color(egui::Color32::RED),
+                                        )
+                                        .on_hover_text(missing);
+                                    }
+                                });
+                                ui.end_row();
+
+                                let soundfonts_guard = soundfonts.lock().unwrap();
+                                let soundfont = soundfonts_guard
+                                    .iter()
+                                    .find(|el| current_soundfont.as_ref() == Some(&el.path))
+                                    .and_then(|el| el.soundfont());
+                                for (channel, current_preset) in
+                                    current_presets.iter_mut().enumerate()
+                                {
+                                    ui.label(format!("Channel {}", channel + 1));
+                                    egui::ComboBox::from_id_source(("preset", channel))
+                                        .width(300.0)
+                                        .selected_text(
+                                            current_preset
                                                 .as_ref()
-                                                .map_or(false, |v| v.2 == formatted);
-                                            let mut response =
-                                                ui.selectable_label(selected, &formatted);
-                                            if response.clicked() {
-                                                *current_preset = Some((
+                                                .map_or("".to_string(), |v| v.2.clone()),
+                                        )
+                                        .show_ui(ui, |ui| {
+                                            let soundfont = match soundfont {
+                                                Some(soundfont) => soundfont,
+                                                None => return,
+                                            };
+                                            // channel without selection plays the default program of the font
+                                            if ui
+                                                .selectable_label(current_preset.is_none(), "-")
+                                                .clicked()
+                                                && current_preset.is_some()
+                                            {
+                                                *current_preset = None;
+                                                reset_synth = true;
+                                            }
+                                            for preset in soundfont.presets.iter() {
+                                                let formatted = format!(
+                                                    "{} - {}   {}",
                                                     preset.banknum(),
                                                     preset.num(),
-                                                    formatted,
-                                                ));
-                                                reset_synth = true;
-                                                response.mark_changed();
+                                                    preset.name()
+                                                );
+                                                let selected = current_preset
+                                                    .as_ref()
+                                                    .map_or(false, |v| v.2 == formatted);
+                                                let mut response =
+                                                    ui.selectable_label(selected, &formatted);
+                                                if response.clicked() {
+                                                    *current_preset = Some((
+                                                        preset.banknum(),
+                                                        preset.num(),
+                                                        formatted,
+                                                    ));
+                                                    reset_synth = true;
+                                                    response.mark_changed();
+                                                }
                                             }
-                                        }
-                                    }
-                                });
-                            ui.end_row();
-
-                            if reset_synth {
-                                let mut synth = synth.lock().unwrap();
-                                // remove current font
-                                synth.font_bank_mut().reset();
-
-                                // select preset or fallback
-                                if current_soundfont.is_some() && current_preset.is_some() {
-                                    let font_id = synth.add_font(
-                                        current_soundfont.as_ref().unwrap().2.clone(),
-                                        true,
-                                    );
-                                    let preset = current_preset.as_ref().unwrap();
-                                    synth
-                                        .program_select(
-                                            0, // TODO: hard-code channel?
-                                            font_id,
-                                            preset.0,
-                                            preset.1.try_into().unwrap(),
-                                        )
-                                        .unwrap();
-                                } else {
-                                    synth.add_font(DEFAULT_SOUNDFONT.clone(), true);
+                                        });
+                                    ui.end_row();
                                 }
-                            }
-                        });
+                                drop(soundfonts_guard);
+
+                                if reset_synth {
+                                    let soundfonts = soundfonts.lock().unwrap();
+                                    synth_slot.send(build_synth(
+                                        sample_rate.load(Ordering::Relaxed),
+                                        &soundfonts,
+                                        &current_soundfont,
+                                        current_presets.as_slice(),
+                                    ));
+                                }
+                            });
+
+                        ui.separator();
+
+                        egui::Grid::new("effects")
+                            .num_columns(2)
+                            .spacing([20.0, 4.0])
+                            .show(ui, |ui| {
+                                effects_ui(ui, &params.effects, setter);
+                            });
+                    });
                 });
             },
         )
@@ -297,87 +449,294 @@ impl Plugin for MyPlugin {
         aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        // allow main thread to lock `Synth` when changing soundfont/preset
-        match self.synth.try_lock().as_mut() {
-            Ok(synth) => self.process_inner(buffer, aux, context, synth),
-            _ => ProcessStatus::KeepAlive,
-        }
+        self.process_inner(buffer, aux, context)
     }
 }
 
 impl MyPlugin {
+    // load files restored from state in background and then apply selection to `Synth`
+    fn reload_soundfonts(&self) {
+        let paths: Vec<PathBuf> = self
+            .params
+            .soundfonts
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|el| matches!(el.status, SoundfontStatus::NotLoaded))
+            .map(|el| el.path.clone())
+            .collect();
+        if paths.is_empty() {
+            return;
+        }
+
+        let params = self.params.clone();
+        let synth_slot = self.synth_slot.clone();
+        let sample_rate = self.sample_rate.clone();
+        let promise = poll_promise::Promise::spawn_thread("reload-soundfont-files", move || {
+            for path in paths {
+                let status = match SoundfontFile::load(&path) {
+                    Ok(soundfont) => SoundfontStatus::Loaded(soundfont),
+                    Err(e) => SoundfontStatus::Missing(e),
+                };
+                let mut soundfonts = params.soundfonts.lock().unwrap();
+                if let Some(el) = soundfonts.iter_mut().find(|el| el.path == path) {
+                    el.status = status;
+                }
+            }
+            // same lock order as gui (soundfont, presets, then soundfonts)
+            let soundfont = params.soundfont.lock().unwrap();
+            let presets = params.presets.lock().unwrap();
+            let soundfonts = params.soundfonts.lock().unwrap();
+            synth_slot.send(build_synth(
+                sample_rate.load(Ordering::Relaxed),
+                &soundfonts,
+                &soundfont,
+                presets.as_slice(),
+            ));
+            Some(())
+        });
+        *self.soundfont_promise.lock().unwrap() = Some(promise);
+    }
+
     fn process_inner(
-        &self,
+        &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
         context: &mut impl ProcessContext<Self>,
-        synth: &mut oxisynth::Synth,
     ) -> ProcessStatus {
         //
-        // handle note on/off
+        // take over synth prepared on gui thread (one at a time until previous fade finishes)
         //
 
-        while let Some(event) = context.next_event() {
-            // TODO: bend and modulation?
-            match event {
-                NoteEvent::NoteOn {
-                    timing: _, // TODO: timing offset
-                    voice_id: _,
-                    channel: _,
-                    note,
-                    velocity,
-                } => {
-                    synth
-                        .send_event(oxisynth::MidiEvent::NoteOn {
-                            channel: 0,
-                            key: note,
-                            vel: denormalize_velocity(velocity) as u8,
-                        })
-                        .unwrap();
-                }
-                NoteEvent::NoteOff {
-                    timing: _,
-                    voice_id: _,
-                    channel: _,
-                    note,
-                    velocity: _,
-                } => {
-                    synth
-                        .send_event(oxisynth::MidiEvent::NoteOff {
-                            channel: 0,
-                            key: note,
-                        })
-                        .unwrap();
-                }
-                _ => {
-                    nih_dbg!("[WARN] unsupported event: {}", event);
-                }
+        if self.fading_synth.is_none() {
+            if let Some(synth) = self.synth_slot.receive() {
+                self.fading_synth = Some(std::mem::replace(&mut self.synth, synth));
+                self.fade_remaining = self.fade_length;
+                self.effects_settings = None;
             }
         }
 
+        let effects_settings = EffectsSettings::new(&self.params.effects);
+        if self.effects_settings != Some(effects_settings) {
+            effects_settings.apply(&mut self.synth);
+            self.effects_settings = Some(effects_settings);
+        }
+
         //
         // synthesize
         //
 
         assert!(buffer.channels() == 2);
 
-        for samples in buffer.iter_samples() {
+        self.events.clear();
+        while let Some(event) = context.next_event() {
+            self.events.push(event);
+        }
+        if let [left, right] = buffer.as_slice() {
+            render(&mut self.synth, &self.events, left, right);
+        }
+
+        for mut samples in buffer.iter_samples() {
             // params
             let gain = self.params.gain.smoothed.next();
 
-            // write left/right samples
-            let mut synth_samples = [0f32; 2];
-            synth.write(&mut synth_samples[..]);
+            // linear crossfade from replaced synth
+            if let Some(fading_synth) = self.fading_synth.as_mut() {
+                let mut fading_samples = [0f32; 2];
+                fading_synth.write(&mut fading_samples[..]);
+                let t = self.fade_remaining as f32 / self.fade_length as f32;
+                for (sample, fading_sample) in samples.iter_mut().zip(fading_samples) {
+                    *sample = (1.0 - t) * *sample + t * fading_sample;
+                }
+                self.fade_remaining -= 1;
+                if self.fade_remaining == 0 {
+                    self.synth_slot.retire(self.fading_synth.take().unwrap());
+                }
+            }
 
-            for (synth_sample, sample) in synth_samples.iter().zip(samples) {
-                *sample = gain * *synth_sample;
+            for sample in samples {
+                *sample *= gain;
             }
         }
 
+        if self.params.editor_state.is_open() {
+            self.analyzer_ring.push_buffer(buffer.as_slice());
+        }
+
         ProcessStatus::Normal
     }
 }
 
+//
+// render
+//
+
+// events are applied right before the sample at their timing,
+// but the synth starts a note only at its next internal block of 64 samples (up to 63 samples late)
+fn render(synth: &mut oxisynth::Synth, events: &[NoteEvent], left: &mut [f32], right: &mut [f32]) {
+    let mut events = events.iter().peekable();
+    for (sample_id, (left, right)) in left.iter_mut().zip(right.iter_mut()).enumerate() {
+        while let Some(event) = events.next_if(|event| event.timing() <= sample_id as u32) {
+            handle_event(synth, *event);
+        }
+        let mut samples = [0f32; 2];
+        synth.write(&mut samples[..]);
+        *left = samples[0];
+        *right = samples[1];
+    }
+
+    // shouldn't happen but don't lose note-off beyond the buffer
+    for event in events {
+        handle_event(synth, *event);
+    }
+}
+
+//
+// handle note on/off
+//
+
+fn handle_event(synth: &mut oxisynth::Synth, event: NoteEvent) {
+    // TODO: bend and modulation?
+    match event {
+        NoteEvent::NoteOn {
+            timing: _,
+            voice_id: _,
+            channel,
+            note,
+            velocity,
+        } => {
+            // channel without preset (e.g. drum channel of a font without bank 128) results in error
+            let _ = synth.send_event(oxisynth::MidiEvent::NoteOn {
+                channel,
+                key: note,
+                vel: denormalize_velocity(velocity) as u8,
+            });
+        }
+        NoteEvent::NoteOff {
+            timing: _,
+            voice_id: _,
+            channel,
+            note,
+            velocity: _,
+        } => {
+            let _ = synth.send_event(oxisynth::MidiEvent::NoteOff { channel, key: note });
+        }
+        // e.g. from general midi files (editor keeps showing its own selection)
+        NoteEvent::MidiProgramChange {
+            timing: _,
+            channel,
+            program,
+        } => {
+            // channel without font/preset results in error, which is fine to ignore
+            let _ = synth.send_event(oxisynth::MidiEvent::ProgramChange {
+                channel,
+                program_id: program,
+            });
+        }
+        _ => {
+            nih_dbg!("[WARN] unsupported event: {}", event);
+        }
+    }
+}
+
+// new synth with selected presets or fallback (e.g. when restored soundfont is missing)
+fn build_synth(
+    sample_rate: f32,
+    soundfonts: &[SoundfontFile],
+    soundfont: &Option<PathBuf>,
+    presets: &[Option<(u32, u32, String)>],
+) -> oxisynth::Synth {
+    let mut synth = oxisynth::Synth::default();
+    synth.set_sample_rate(sample_rate);
+
+    let soundfont = soundfonts
+        .iter()
+        .find(|el| soundfont.as_ref() == Some(&el.path))
+        .and_then(|el| el.soundfont());
+    if let Some(soundfont) = soundfont {
+        // channels without selection get default program of the font
+        let font_id = synth.add_font(soundfont.clone(), true);
+        for (channel, preset) in presets.iter().enumerate() {
+            if let Some(preset) = preset {
+                // preset can be gone from the file since the state was saved (then default program stays)
+                if let Ok(preset_num) = preset.1.try_into() {
+                    let _ = synth.program_select(channel as u8, font_id, preset.0, preset_num);
+                }
+            }
+        }
+        return synth;
+    }
+    synth.add_font(DEFAULT_SOUNDFONT.clone(), true);
+    synth
+}
+
 fn denormalize_velocity(v: f32) -> f32 {
     (v * 127.0).round().clamp(0.0, 127.0)
 }
+
+//
+// combo_box_for_enum_param
+//
+
+fn combo_box_for_enum_param<
+    T: nih_plug::params::enums::Enum + std::cmp::PartialEq + Copy + 'static,
+>(
+    combo_box: egui::ComboBox,
+    ui: &mut egui::Ui,
+    param: &EnumParam<T>,
+    setter: &ParamSetter,
+) {
+    let mut selected = param.value();
+    let selected_before = selected;
+    combo_box
+        .selected_text(T::variants()[selected.to_index()])
+        .show_ui(ui, |ui| {
+            for (index, &variant) in T::variants().iter().enumerate() {
+                ui.selectable_value(&mut selected, T::from_index(index), variant);
+            }
+        });
+    if selected != selected_before {
+        setter.begin_set_parameter(param);
+        setter.set_parameter(param, selected);
+        setter.end_set_parameter(param);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 44100.0;
+
+    // `oxisynth::Synth` renders this many samples at once internally
+    const SYNTH_BLOCK_SIZE: usize = 64;
+
+    fn note_on(timing: u32, note: u8) -> NoteEvent {
+        NoteEvent::NoteOn {
+            timing,
+            voice_id: None,
+            channel: 0,
+            note,
+            velocity: 1.0,
+        }
+    }
+
+    #[test]
+    fn render_starts_notes_at_timing() {
+        // onset is allowed to be late until the synth's next block (cf. `render`)
+        for timing in [0, 1, 1000, 4096, 8000] {
+            let mut synth = build_synth(SAMPLE_RATE, &[], &None, &[]);
+            let mut left = vec![0.0; 8192];
+            let mut right = vec![0.0; 8192];
+            render(&mut synth, &[note_on(timing, 69)], &mut left, &mut right);
+            let onset = left.iter().position(|sample| *sample != 0.0).unwrap();
+            assert!(
+                (timing as usize..timing as usize + SYNTH_BLOCK_SIZE).contains(&onset),
+                "{} {}",
+                timing,
+                onset
+            );
+            assert_eq!(right.iter().position(|sample| *sample != 0.0), Some(onset));
+        }
+    }
+}
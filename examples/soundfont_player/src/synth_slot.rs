@@ -0,0 +1,122 @@
+use std::{
+    ptr,
+    sync::atomic::{AtomicPtr, Ordering},
+};
+
+// hands `Synth` prepared on gui thread (soundfont/preset change) over to audio thread without locking.
+// audio thread returns the replaced one so that it's deallocated off the audio thread.
+
+// between two `send`s, at most the synth fading out at the time and the one replaced by the pending one get retired,
+// so nothing has to collect them other than the next `send`
+const RETIRED_CAPACITY: usize = 2;
+
+//
+// SynthSlot
+//
+
+#[derive(Debug, Default)]
+pub(crate) struct SynthSlot {
+    // sent from gui thread (latest one wins)
+    pending: AtomicPtr<oxisynth::Synth>,
+    // returned from audio thread to be dropped on gui thread
+    retired: [AtomicPtr<oxisynth::Synth>; RETIRED_CAPACITY],
+}
+
+impl SynthSlot {
+    // gui thread
+    pub(crate) fn send(&self, synth: oxisynth::Synth) {
+        self.collect();
+        let old = self
+            .pending
+            .swap(Box::into_raw(Box::new(synth)), Ordering::AcqRel);
+        drop_raw(old);
+    }
+
+    // gui thread
+    pub(crate) fn collect(&self) {
+        for retired in self.retired.iter() {
+            let old = retired.swap(ptr::null_mut(), Ordering::AcqRel);
+            drop_raw(old);
+        }
+    }
+
+    // audio thread (holds off while retired ones are full so that `retire` always has room)
+    pub(crate) fn receive(&self) -> Option<Box<oxisynth::Synth>> {
+        let has_room = self
+            .retired
+            .iter()
+            .any(|retired| retired.load(Ordering::Acquire).is_null());
+        if !has_room {
+            return None;
+        }
+        let new = self.pending.swap(ptr::null_mut(), Ordering::AcqRel);
+        if new.is_null() {
+            return None;
+        }
+        // SAFETY: non-null pointers in the slot always come from `Box::into_raw` and are taken only once by `swap`
+        Some(unsafe { Box::from_raw(new) })
+    }
+
+    // audio thread (at most one synth is retired per `receive`)
+    pub(crate) fn retire(&self, synth: Box<oxisynth::Synth>) {
+        let synth = Box::into_raw(synth);
+        for retired in self.retired.iter() {
+            if retired
+                .compare_exchange(ptr::null_mut(), synth, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+            {
+                return;
+            }
+        }
+        debug_assert!(false, "no room for retired synth");
+        drop_raw(synth);
+    }
+}
+
+impl Drop for SynthSlot {
+    fn drop(&mut self) {
+        drop_raw(*self.pending.get_mut());
+        for retired in self.retired.iter_mut() {
+            drop_raw(*retired.get_mut());
+        }
+    }
+}
+
+fn drop_raw(synth: *mut oxisynth::Synth) {
+    if !synth.is_null() {
+        // SAFETY: same as `SynthSlot::receive`
+        drop(unsafe { Box::from_raw(synth) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn send_during_fade_without_collector() {
+        let slot = SynthSlot::default();
+        let mut synth = Box::<oxisynth::Synth>::default();
+
+        // first switch starts fading out the initial synth
+        slot.send(oxisynth::Synth::default());
+        let fading = std::mem::replace(&mut synth, slot.receive().unwrap());
+
+        // second switch arrives during the fade (nothing retired to collect yet) and nobody collects afterwards
+        slot.send(oxisynth::Synth::default());
+        slot.retire(fading);
+
+        // second synth is still taken over once the fade finishes
+        let fading = std::mem::replace(&mut synth, slot.receive().unwrap());
+        slot.retire(fading);
+        assert!(slot.receive().is_none());
+
+        // next `send` drops whatever was retired meanwhile
+        slot.send(oxisynth::Synth::default());
+        assert!(slot
+            .retired
+            .iter()
+            .all(|retired| retired.load(Ordering::Acquire).is_null()));
+        assert!(slot.receive().is_some());
+    }
+}
@@ -0,0 +1,630 @@
+use nih_plug::prelude::*;
+use nih_plug_egui::{egui, widgets};
+use serde::{Deserialize, Serialize};
+use std::{path::Path, sync::Mutex};
+
+// cf. https://www.huygens-fokker.org/scala/scl_format.html
+//     https://www.huygens-fokker.org/scala/help.htm#mappings
+
+//
+// Scale
+//
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ScaleFields")]
+pub struct Scale {
+    pub name: String,
+    pub description: String,
+    // degrees 1..=n in cents where the last one is the period (usually 1200)
+    cents: Vec<f64>,
+}
+
+// restored state goes through the same check as parsed file
+#[derive(Deserialize)]
+struct ScaleFields {
+    name: String,
+    description: String,
+    cents: Vec<f64>,
+}
+
+impl TryFrom<ScaleFields> for Scale {
+    type Error = String;
+
+    fn try_from(fields: ScaleFields) -> Result<Self, String> {
+        Self::new(fields.name, fields.description, fields.cents)
+    }
+}
+
+impl Scale {
+    pub fn equal_temperament() -> Self {
+        Self {
+            name: "12-TET".to_string(),
+            description: "12 tone equal temperament".to_string(),
+            cents: (1..=12).map(|degree| 100.0 * degree as f64).collect(),
+        }
+    }
+
+    // at least the period is required
+    fn new(name: String, description: String, cents: Vec<f64>) -> Result<Self, String> {
+        if cents.is_empty() {
+            return Err("empty scale".to_string());
+        }
+        Ok(Self {
+            name,
+            description,
+            cents,
+        })
+    }
+
+    // blocking, so call it off the audio thread
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(file_name(path), &text)
+    }
+
+    pub fn parse(name: String, text: &str) -> Result<Self, String> {
+        // empty description line is allowed, so only comments are skipped
+        let mut lines = text.lines().filter(|line| !line.starts_with('!'));
+        let description = lines
+            .next()
+            .ok_or("missing description")?
+            .trim()
+            .to_string();
+        let count: usize = lines
+            .next()
+            .and_then(|line| line.trim().parse().ok())
+            .ok_or("invalid number of notes")?;
+
+        let mut cents = Vec::with_capacity(count);
+        for line in lines.take(count) {
+            let pitch = line.split_whitespace().next().unwrap_or("");
+            cents.push(parse_pitch(pitch).ok_or_else(|| format!("invalid pitch '{}'", pitch))?);
+        }
+        if cents.len() < count {
+            return Err(format!(
+                "expected {} notes but found {}",
+                count,
+                cents.len()
+            ));
+        }
+
+        Self::new(name, description, cents)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cents.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cents.is_empty()
+    }
+
+    // degree can go beyond the period in both directions
+    fn degree_cents(&self, degree: i32) -> f64 {
+        let n = self.cents.len() as i32;
+        let period = self.cents[self.cents.len() - 1];
+        let index = degree.rem_euclid(n);
+        let base = if index == 0 {
+            0.0
+        } else {
+            self.cents[(index - 1) as usize]
+        };
+        degree.div_euclid(n) as f64 * period + base
+    }
+}
+
+// cents if it has period, otherwise ratio "a/b" or integer "a"
+fn parse_pitch(pitch: &str) -> Option<f64> {
+    if pitch.contains('.') {
+        return pitch.parse().ok();
+    }
+    let (numerator, denominator) = pitch.split_once('/').unwrap_or((pitch, "1"));
+    let numerator: f64 = numerator.parse::<u64>().ok()? as f64;
+    let denominator: f64 = denominator.parse::<u64>().ok()? as f64;
+    if numerator <= 0.0 || denominator <= 0.0 {
+        return None;
+    }
+    Some(1200.0 * (numerator / denominator).log2())
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map_or("".to_string(), |name| name.to_string_lossy().to_string())
+}
+
+//
+// KeyboardMapping
+//
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyboardMapping {
+    pub name: String,
+    first_note: u8,
+    last_note: u8,
+    // key where scale degree 0 is mapped
+    middle_note: u8,
+    pub reference_note: u8,
+    pub reference_frequency: f32,
+    // scale degree which repeats the mapping (0 means the scale's period)
+    octave_degree: usize,
+    // scale degree for each key from `middle_note` (empty means linear mapping)
+    mapping: Vec<Option<usize>>,
+}
+
+impl KeyboardMapping {
+    // every key to the next degree with A4 = 440 Hz
+    pub fn linear() -> Self {
+        Self {
+            name: "Linear".to_string(),
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: 440.0,
+            octave_degree: 0,
+            mapping: vec![],
+        }
+    }
+
+    // blocking, so call it off the audio thread
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(file_name(path), &text)
+    }
+
+    pub fn parse(name: String, text: &str) -> Result<Self, String> {
+        let mut values = text
+            .lines()
+            .filter(|line| !line.starts_with('!') && !line.trim().is_empty())
+            .map(|line| line.split_whitespace().next().unwrap_or(""));
+        let mut next = |label: &str| {
+            values
+                .next()
+                .ok_or_else(|| format!("missing {}", label))
+                .map(|value| value.to_string())
+        };
+        let note = |value: String, label: &str| -> Result<u8, String> {
+            value
+                .parse::<u8>()
+                .ok()
+                .filter(|note| *note <= 127)
+                .ok_or_else(|| format!("invalid {} '{}'", label, value))
+        };
+
+        let size: usize = next("map size")?
+            .parse()
+            .map_err(|_| "invalid map size".to_string())?;
+        let first_note = note(next("first note")?, "first note")?;
+        let last_note = note(next("last note")?, "last note")?;
+        let middle_note = note(next("middle note")?, "middle note")?;
+        let reference_note = note(next("reference note")?, "reference note")?;
+        let reference_frequency: f32 = next("reference frequency")?
+            .parse()
+            .ok()
+            .filter(|frequency: &f32| *frequency > 0.0)
+            .ok_or("invalid reference frequency")?;
+        let octave_degree: usize = next("octave degree")?
+            .parse()
+            .map_err(|_| "invalid octave degree".to_string())?;
+
+        // missing entries at the end are unmapped
+        let mut mapping = vec![None; size];
+        for entry in mapping.iter_mut() {
+            match values.next() {
+                Some("x") | None => {}
+                Some(value) => {
+                    *entry = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("invalid mapping '{}'", value))?,
+                    );
+                }
+            }
+        }
+
+        Ok(Self {
+            name,
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    // `None` for unmapped key
+    fn cents(&self, scale: &Scale, note: u8) -> Option<f64> {
+        if note < self.first_note || note > self.last_note {
+            return None;
+        }
+        let offset = note as i32 - self.middle_note as i32;
+        if self.mapping.is_empty() {
+            return Some(scale.degree_cents(offset));
+        }
+        let size = self.mapping.len() as i32;
+        let degree = self.mapping[offset.rem_euclid(size) as usize]? as i32;
+        let octave_degree = if self.octave_degree == 0 {
+            scale.len()
+        } else {
+            self.octave_degree
+        };
+        Some(
+            offset.div_euclid(size) as f64 * scale.degree_cents(octave_degree as i32)
+                + scale.degree_cents(degree),
+        )
+    }
+}
+
+//
+// TuningTable
+//
+
+// ratio to reference pitch for each midi note (copied to audio thread)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TuningTable {
+    // zero for unmapped key
+    ratios: [f32; 128],
+}
+
+impl TuningTable {
+    fn new(scale: &Scale, mapping: &KeyboardMapping) -> Self {
+        let reference = mapping.cents(scale, mapping.reference_note).unwrap_or(0.0);
+        let mut ratios = [0.0; 128];
+        for (note, ratio) in ratios.iter_mut().enumerate() {
+            if let Some(cents) = mapping.cents(scale, note as u8) {
+                *ratio = ((cents - reference) / 1200.0).exp2() as f32;
+            }
+        }
+        Self { ratios }
+    }
+
+    // `reference_pitch` is the frequency of reference note (A4 by default)
+    pub fn frequency(&self, note: u8, reference_pitch: f32) -> Option<f32> {
+        let ratio = self.ratios[(note as usize).min(127)];
+        if ratio > 0.0 {
+            Some(reference_pitch * ratio)
+        } else {
+            None
+        }
+    }
+}
+
+impl Default for TuningTable {
+    fn default() -> Self {
+        Self::new(&Scale::equal_temperament(), &KeyboardMapping::linear())
+    }
+}
+
+//
+// Tuning
+//
+
+// loaded files (persisted in plugin state as `Mutex<Tuning>`) and the table derived from them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "TuningFiles", into = "TuningFiles")]
+pub struct Tuning {
+    files: TuningFiles,
+    table: TuningTable,
+}
+
+// only files are serialized and the table is recomputed on restore
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct TuningFiles {
+    scale: Option<Scale>,
+    mapping: Option<KeyboardMapping>,
+}
+
+impl From<TuningFiles> for Tuning {
+    fn from(files: TuningFiles) -> Self {
+        let table = TuningTable::new(
+            files.scale.as_ref().unwrap_or(&Scale::equal_temperament()),
+            files.mapping.as_ref().unwrap_or(&KeyboardMapping::linear()),
+        );
+        Self { files, table }
+    }
+}
+
+impl From<Tuning> for TuningFiles {
+    fn from(tuning: Tuning) -> Self {
+        tuning.files
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        TuningFiles::default().into()
+    }
+}
+
+impl Tuning {
+    pub fn scale(&self) -> Option<&Scale> {
+        self.files.scale.as_ref()
+    }
+
+    pub fn mapping(&self) -> Option<&KeyboardMapping> {
+        self.files.mapping.as_ref()
+    }
+
+    pub fn table(&self) -> TuningTable {
+        self.table
+    }
+
+    pub fn set_scale(&mut self, scale: Option<Scale>) {
+        *self = TuningFiles {
+            scale,
+            mapping: self.files.mapping.take(),
+        }
+        .into();
+    }
+
+    pub fn set_mapping(&mut self, mapping: Option<KeyboardMapping>) {
+        *self = TuningFiles {
+            scale: self.files.scale.take(),
+            mapping,
+        }
+        .into();
+    }
+
+    // audio thread (keep the current table if gui is holding the lock)
+    pub fn try_update(tuning: &Mutex<Tuning>, table: &mut TuningTable) {
+        if let Ok(tuning) = tuning.try_lock() {
+            *table = tuning.table;
+        }
+    }
+}
+
+//
+// reference pitch parameter
+//
+
+pub fn reference_pitch_param(default: f32) -> FloatParam {
+    FloatParam::new(
+        "Reference Pitch",
+        default,
+        FloatRange::Skewed {
+            min: 100.0,
+            max: 1000.0,
+            factor: FloatRange::skew_factor(-1.0),
+        },
+    )
+    .with_unit(" Hz")
+    .with_value_to_string(formatters::v2s_f32_rounded(2))
+}
+
+//
+// TuningView
+//
+
+// gui state (kept as egui editor's user state to track asynchronous loading)
+pub struct TuningView {
+    // reference frequency of the loaded .kbm file to be applied to the parameter
+    // (wrapped by `Mutex` since editor's user state has to be `Sync`)
+    promise: Mutex<Option<poll_promise::Promise<Result<Option<f32>, String>>>>,
+}
+
+impl TuningView {
+    pub fn new() -> Self {
+        Self {
+            promise: Mutex::new(None),
+        }
+    }
+
+    // adds rows to 2-column grid
+    pub fn ui(
+        &mut self,
+        ui: &mut egui::Ui,
+        tuning: &std::sync::Arc<Mutex<Tuning>>,
+        reference_pitch: &FloatParam,
+        setter: &ParamSetter,
+    ) {
+        let mut promise = self.promise.lock().unwrap();
+        let mut is_loading = false;
+        let mut error = None;
+        if let Some(current) = promise.as_ref() {
+            match current.ready() {
+                None => {
+                    is_loading = true;
+                }
+                Some(Ok(reference_frequency)) => {
+                    if let Some(reference_frequency) = *reference_frequency {
+                        setter.begin_set_parameter(reference_pitch);
+                        setter.set_parameter(reference_pitch, reference_frequency);
+                        setter.end_set_parameter(reference_pitch);
+                    }
+                    // reset promise on success
+                    *promise = None;
+                }
+                Some(Err(e)) => {
+                    error = Some(e.clone());
+                }
+            }
+        }
+
+        ui.label("Tuning");
+        ui.horizontal(|ui| {
+            let label = {
+                let tuning = tuning.lock().unwrap();
+                let scale = tuning.scale().map_or("12-TET", |scale| &scale.name);
+                match tuning.mapping() {
+                    Some(mapping) => format!("{} / {}", scale, mapping.name),
+                    None => scale.to_string(),
+                }
+            };
+            ui.label(label);
+
+            if ui
+                .button(if is_loading {
+                    "Loading…"
+                } else {
+                    "Load File"
+                })
+                .on_hover_text("scale (.scl) or keyboard mapping (.kbm)")
+                .clicked()
+                && !is_loading
+            {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("Scala", &["scl", "kbm"])
+                    .pick_file()
+                {
+                    let tuning = tuning.clone();
+                    *promise = Some(poll_promise::Promise::spawn_thread(
+                        "load-tuning-file",
+                        move || {
+                            let is_mapping = path
+                                .extension()
+                                .map_or(false, |extension| extension.eq_ignore_ascii_case("kbm"));
+                            if is_mapping {
+                                let mapping = KeyboardMapping::load(&path)?;
+                                let reference_frequency = mapping.reference_frequency;
+                                tuning.lock().unwrap().set_mapping(Some(mapping));
+                                Ok(Some(reference_frequency))
+                            } else {
+                                let scale = Scale::load(&path)?;
+                                tuning.lock().unwrap().set_scale(Some(scale));
+                                Ok(None)
+                            }
+                        },
+                    ));
+                }
+            }
+
+            if ui.button("Reset").clicked() && !is_loading {
+                *tuning.lock().unwrap() = Tuning::default();
+                *promise = None;
+            }
+
+            if let Some(error) = error {
+                ui.label(egui::RichText::new("ERROR").color(egui::Color32::RED))
+                    .on_hover_text(error);
+            }
+        });
+        ui.end_row();
+
+        ui.label("Reference");
+        ui.add(widgets::ParamSlider::for_param(reference_pitch, setter));
+        ui.end_row();
+    }
+}
+
+impl Default for TuningView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn scale_ratio_and_cents() {
+        let text = "\
+! example.scl
+!
+Just major triad and a quarter tone
+ 4
+!
+ 150.0 quarter tone
+ 5/4
+ 3/2  fifth
+ 2
+";
+        let scale = Scale::parse("example.scl".to_string(), text).unwrap();
+        assert_eq!(scale.description, "Just major triad and a quarter tone");
+        assert_eq!(scale.len(), 4);
+        assert_close(scale.degree_cents(1), 150.0);
+        assert_close(scale.degree_cents(2), 1200.0 * 1.25f64.log2());
+        assert_close(scale.degree_cents(3), 1200.0 * 1.5f64.log2());
+        assert_close(scale.degree_cents(4), 1200.0);
+        // beyond the period in both directions
+        assert_close(scale.degree_cents(5), 1350.0);
+        assert_close(scale.degree_cents(-1), -1200.0 + 1200.0 * 1.5f64.log2());
+    }
+
+    #[test]
+    fn scale_wrong_note_count() {
+        let text = "too few\n3\n100.0\n2/1\n";
+        assert_eq!(
+            Scale::parse("".to_string(), text),
+            Err("expected 3 notes but found 2".to_string())
+        );
+        assert_eq!(
+            Scale::parse("".to_string(), "empty\n0\n"),
+            Err("empty scale".to_string())
+        );
+        assert_eq!(
+            Scale::parse("".to_string(), "bad pitch\n1\n-3/2\n"),
+            Err("invalid pitch '-3/2'".to_string())
+        );
+    }
+
+    #[test]
+    fn scale_restore_rejects_empty() {
+        let fields = ScaleFields {
+            name: "".to_string(),
+            description: "".to_string(),
+            cents: vec![],
+        };
+        assert_eq!(Scale::try_from(fields), Err("empty scale".to_string()));
+    }
+
+    #[test]
+    fn mapping_unmapped_keys() {
+        // white keys only on 12-TET with unmapped black keys
+        let text = "\
+! white.kbm
+12
+0
+127
+60
+69
+440.0
+12
+! mapping
+0
+x
+2
+x
+4
+5
+x
+7
+x
+9
+x
+11
+";
+        let mapping = KeyboardMapping::parse("white.kbm".to_string(), text).unwrap();
+        assert_eq!(mapping.reference_frequency, 440.0);
+        let table = TuningTable::new(&Scale::equal_temperament(), &mapping);
+        assert_eq!(table.frequency(69, 440.0), Some(440.0));
+        assert_eq!(table.frequency(70, 440.0), None);
+        assert_eq!(table.frequency(61, 440.0), None);
+        let c4 = table.frequency(60, 440.0).unwrap();
+        assert!((c4 - 261.6256).abs() < 1e-3, "{}", c4);
+        let c5 = table.frequency(72, 440.0).unwrap();
+        assert!((c5 - 2.0 * c4).abs() < 1e-3, "{}", c5);
+    }
+
+    #[test]
+    fn mapping_missing_entries_are_unmapped() {
+        let text = "4\n0\n127\n60\n60\n261.63\n0\n0\n1\n";
+        let mapping = KeyboardMapping::parse("".to_string(), text).unwrap();
+        assert_eq!(mapping.mapping, vec![Some(0), Some(1), None, None]);
+        assert_eq!(
+            KeyboardMapping::parse("".to_string(), "1\n0\n128\n"),
+            Err("invalid last note '128'".to_string())
+        );
+    }
+}